@@ -0,0 +1,269 @@
+//! Render a simulation into a sequence of PNG or SVG images, or an animated GIF.
+//!
+//! ```console
+//! $ cargo run --release --bin render -- --preset torus --frames 300 --out frames
+//! $ ffmpeg -framerate 60 -i frames/frame-%05d.png boids.mp4
+//! $ cargo run --release --bin render -- --frames 100 --every 3 --image 640x360 --gif boids.gif
+//! ```
+//!
+//! No browser is needed, PNG images are drawn by a software rasteriser.
+//! The debug overlay is not rendered.
+
+#[cfg(not(target_arch = "wasm32"))]
+use boids::headless::{
+    ColorMode, Detail, Preset, Recording, Runner, Shape, Sprite, ThemeKind, STEP_SECS,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{env, fs, path::PathBuf, process};
+
+#[cfg(not(target_arch = "wasm32"))]
+const USAGE: &str = "\
+Usage: render [OPTIONS]
+
+Options:
+    --preset NAME             Preset to start from, defaults are used otherwise
+    --seed N                  Seed used to place the boids [default: 0]
+    --warmup N                Steps before the first frame [default: 0]
+    --frames N                Number of images to write [default: 1]
+    --every N                 Steps between two images [default: 1]
+    --dt SECS                 Length of a single step [default: 0.01, as in the browser]
+    --size WIDTHxHEIGHT       Size of the world [default: 1280x720]
+    --image WIDTHxHEIGHT      Size of the images [default: size of the world]
+    --color MODE              Colour boids by flock, speed, heading, density,
+                              neighbours or stress [default: uniform]
+    --theme NAME              Colours to draw with: light, dark or high-contrast
+                              [default: the theme of the preset or light]
+    --shape NAME              Draw boids as triangle, arrow, dot, fish or bird
+                              [default: triangle]
+    --scale N                 Size of the boids relative to the default [default: 1]
+    --sprite FILE             Draw boids as the given PNG image instead
+    --upright                 Do not turn the image to the heading of the boid
+    --detail NAME             Draw boids in full, as dots, as pixels or pick
+                              automatically by number and size [default: full]
+    --trail N                 Draw the last N positions of every boid [default: 0]
+    --svg                     Write SVG instead of PNG images
+    --gif FILE                Write a single animated GIF instead of images
+    --fps N                   Frame rate of the GIF [default: one frame every --every steps]
+    --out DIR                 Directory to write the images to [default: frames]";
+
+#[cfg(not(target_arch = "wasm32"))]
+struct Options {
+    preset: Option<Preset>,
+    seed: u64,
+    warmup: usize,
+    frames: usize,
+    every: usize,
+    dt: f64,
+    size: (f64, f64),
+    image: Option<(u32, u32)>,
+    color: ColorMode,
+    theme: Option<ThemeKind>,
+    shape: Shape,
+    scale: f64,
+    sprite: Option<PathBuf>,
+    upright: bool,
+    detail: Detail,
+    trail: usize,
+    svg: bool,
+    gif: Option<PathBuf>,
+    fps: Option<f64>,
+    out: PathBuf,
+}
+
+/// The software rasteriser needs a native target.
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    panic!("The render tool only runs natively");
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    let options = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        process::exit(1);
+    });
+    if let Err(err) = render(&options) {
+        eprintln!("Failed to render: {}", err);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn render(options: &Options) -> Result<(), String> {
+    let mut recording = None;
+    if let Some(path) = &options.gif {
+        let (width, height) = image_size(options);
+        let fps = options
+            .fps
+            .unwrap_or(1.0 / (options.dt * options.every.max(1) as f64));
+        let gif =
+            Recording::new(width as u16, height as u16, fps).map_err(|err| err.to_string())?;
+        recording = Some((path, gif));
+    } else {
+        fs::create_dir_all(&options.out).map_err(|err| err.to_string())?;
+    }
+    let mut runner = Runner::new(options.size.0, options.size.1, options.seed, |boids| {
+        if let Some(preset) = options.preset {
+            preset.apply(boids);
+        }
+        boids.trail_length = options.trail;
+    });
+    let preset_theme = options.preset.and_then(|preset| preset.theme());
+    if let Some(kind) = options.theme.or(preset_theme) {
+        runner.theme.kind = kind;
+    }
+    // Only colouring needs flocks, skip detecting them otherwise
+    runner
+        .boids
+        .set_track_flocks(options.color == ColorMode::Flock);
+    runner.appearance.shape = options.shape;
+    runner.appearance.scale = options.scale;
+    runner.appearance.rotate_sprite = !options.upright;
+    runner.detail = options.detail;
+    if let Some(path) = &options.sprite {
+        runner.appearance.shape = Shape::Sprite;
+        runner.sprite = Some(Sprite::load_png(path)?);
+    }
+    let (width, height) = image_size(options);
+    for _ in 0..options.warmup {
+        runner.step(options.dt);
+    }
+    for frame in 0..options.frames {
+        if frame > 0 {
+            for _ in 0..options.every {
+                runner.step(options.dt);
+            }
+        }
+        if let Some((_, gif)) = &mut recording {
+            let pixmap = runner
+                .render(width, height, options.color)
+                .ok_or("Invalid image size")?;
+            // Encode right away to keep only one frame in memory
+            gif.push(pixmap.take());
+            gif.encode_pending().map_err(|err| err.to_string())?;
+        } else if options.svg {
+            let path = options.out.join(format!("frame-{:05}.svg", frame));
+            let svg = runner.to_svg(width as f64, height as f64, options.color);
+            fs::write(&path, svg).map_err(|err| format!("{}: {}", path.display(), err))?;
+        } else {
+            let path = options.out.join(format!("frame-{:05}.png", frame));
+            runner
+                .render(width, height, options.color)
+                .ok_or("Invalid image size")?
+                .save_png(&path)
+                .map_err(|err| format!("{}: {}", path.display(), err))?;
+        }
+    }
+    if let Some((path, gif)) = recording {
+        let gif = gif.finish().map_err(|err| err.to_string())?;
+        fs::write(path, gif).map_err(|err| format!("{}: {}", path.display(), err))?;
+    }
+    Ok(())
+}
+
+/// Size of the images in pixels.
+#[cfg(not(target_arch = "wasm32"))]
+fn image_size(options: &Options) -> (u32, u32) {
+    options
+        .image
+        .unwrap_or((options.size.0 as u32, options.size.1 as u32))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        preset: None,
+        seed: 0,
+        warmup: 0,
+        frames: 1,
+        every: 1,
+        dt: STEP_SECS,
+        size: (1280.0, 720.0),
+        image: None,
+        color: ColorMode::Uniform,
+        theme: None,
+        shape: Shape::Triangle,
+        scale: 1.0,
+        sprite: None,
+        upright: false,
+        detail: Detail::Full,
+        trail: 0,
+        svg: false,
+        gif: None,
+        fps: None,
+        out: PathBuf::from("frames"),
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--preset" => {
+                let name = value()?;
+                let parsed = name
+                    .parse()
+                    .map_err(|_| format!("Unknown preset {}", name))?;
+                options.preset = Some(parsed);
+            }
+            "--seed" => options.seed = parse_number(&value()?)?,
+            "--warmup" => options.warmup = parse_number(&value()?)?,
+            "--frames" => options.frames = parse_number(&value()?)?,
+            "--every" => options.every = parse_number(&value()?)?,
+            "--dt" => options.dt = parse_number(&value()?)?,
+            "--size" => options.size = parse_size(&value()?)?,
+            "--image" => options.image = Some(parse_size(&value()?)?),
+            "--color" => {
+                let name = value()?;
+                options.color = name
+                    .parse()
+                    .map_err(|_| format!("Unknown colour mode {}", name))?;
+            }
+            "--theme" => {
+                let name = value()?;
+                let parsed = name
+                    .parse()
+                    .map_err(|_| format!("Unknown theme {}", name))?;
+                options.theme = Some(parsed);
+            }
+            "--shape" => {
+                let name = value()?;
+                options.shape = name
+                    .parse()
+                    .map_err(|_| format!("Unknown shape {}", name))?;
+            }
+            "--scale" => options.scale = parse_number(&value()?)?,
+            "--sprite" => options.sprite = Some(PathBuf::from(value()?)),
+            "--upright" => options.upright = true,
+            "--detail" => {
+                let name = value()?;
+                options.detail = name
+                    .parse()
+                    .map_err(|_| format!("Unknown level of detail {}", name))?;
+            }
+            "--trail" => options.trail = parse_number(&value()?)?,
+            "--svg" => options.svg = true,
+            "--gif" => options.gif = Some(PathBuf::from(value()?)),
+            "--fps" => options.fps = Some(parse_number(&value()?)?),
+            "--out" => options.out = PathBuf::from(value()?),
+            "--help" | "-h" => return Err(String::from("Render a simulation to images")),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+/// Parse `WIDTHxHEIGHT`.
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_size<T: std::str::FromStr>(raw: &str) -> Result<(T, T), String> {
+    let mut parts = raw.splitn(2, 'x');
+    let width = parse_number(parts.next().unwrap_or(""))?;
+    let height = parse_number(parts.next().unwrap_or(""))?;
+    Ok((width, height))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_number<T: std::str::FromStr>(raw: &str) -> Result<T, String> {
+    raw.parse().map_err(|_| format!("Invalid number {}", raw))
+}
@@ -0,0 +1,226 @@
+//! Sweep one or two parameters over a grid and record order parameters.
+//!
+//! ```console
+//! $ cargo run --release --bin sweep -- --x align-factor=0:2:5 --y cohesion-radius=20:200:4
+//! ```
+//!
+//! Every configuration is run with several seeds. After a warmup the
+//! metrics are sampled regularly, averaged per run and summarised over
+//! all seeds. The result is written as tab separated table.
+
+use boids::headless::{param_by_key, Metrics, Param, Preset, Runner, Summary, STEP_SECS};
+
+use std::{
+    env,
+    fs::File,
+    io::{self, Write},
+    process,
+};
+
+const USAGE: &str = "\
+Usage: sweep --x KEY=START:END:COUNT [OPTIONS]
+
+Options:
+    --x KEY=START:END:COUNT   First parameter to sweep, i.e. align-factor=0:2:5
+    --y KEY=START:END:COUNT   Optional second parameter to sweep
+    --preset NAME             Preset to start from, defaults are used otherwise
+    --seeds N                 Number of runs per configuration [default: 3]
+    --steps N                 Number of steps per run [default: 2000]
+    --warmup N                Steps before metrics are recorded [default: 500]
+    --sample-every N          Steps between recorded metrics [default: 10]
+    --dt SECS                 Length of a single step [default: 0.01, as in the browser]
+    --size WIDTHxHEIGHT       Size of the world [default: 1280x720]
+    --out FILE                Write the table to FILE instead of stdout";
+
+/// A parameter and the values it takes.
+struct Axis {
+    param: &'static Param,
+    values: Vec<f64>,
+}
+
+struct Options {
+    x: Axis,
+    y: Option<Axis>,
+    preset: Option<Preset>,
+    seeds: u64,
+    steps: usize,
+    warmup: usize,
+    sample_every: usize,
+    dt: f64,
+    size: (f64, f64),
+    out: Option<String>,
+}
+
+/// Name and getter of a recorded quantity
+type Quantity = (&'static str, fn(&Metrics) -> f64);
+
+/// Quantities recorded for every configuration.
+const QUANTITIES: [Quantity; 6] = [
+    ("polarization", |m| m.polarization),
+    ("milling", |m| m.milling),
+    ("nn_distance", |m| m.mean_nn_distance),
+    ("clusters", |m| m.clusters as f64),
+    ("speed", |m| m.mean_speed),
+    ("density", |m| m.density),
+];
+
+fn main() {
+    let options = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        process::exit(1);
+    });
+    let result = match &options.out {
+        Some(path) => File::create(path).and_then(|file| sweep(&options, file)),
+        None => sweep(&options, io::stdout().lock()),
+    };
+    if let Err(err) = result {
+        eprintln!("Failed to write table: {}", err);
+        process::exit(1);
+    }
+}
+
+fn sweep(options: &Options, mut out: impl Write) -> io::Result<()> {
+    write!(out, "{}", options.x.param.key)?;
+    if let Some(y) = &options.y {
+        write!(out, "\t{}", y.param.key)?;
+    }
+    for (name, _) in QUANTITIES.iter() {
+        write!(out, "\t{}_mean\t{}_std", name, name)?;
+    }
+    writeln!(out)?;
+    let y_values = match &options.y {
+        Some(y) => y.values.iter().map(|val| Some((y.param, *val))).collect(),
+        None => vec![None],
+    };
+    for x in &options.x.values {
+        for y in &y_values {
+            let runs: Vec<_> = (0..options.seeds)
+                .map(|seed| run(options, (options.x.param, *x), *y, seed))
+                .collect();
+            write!(out, "{}", x)?;
+            if let Some((_, y)) = y {
+                write!(out, "\t{}", y)?;
+            }
+            for idx in 0..QUANTITIES.len() {
+                let summary = Summary::of(runs.iter().map(|run| run[idx]));
+                write!(out, "\t{:.4}\t{:.4}", summary.mean, summary.std_dev)?;
+            }
+            writeln!(out)?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Simulate a single configuration and return the mean of every quantity.
+fn run(
+    options: &Options,
+    x: (&'static Param, f64),
+    y: Option<(&'static Param, f64)>,
+    seed: u64,
+) -> Vec<f64> {
+    let mut runner = Runner::new(options.size.0, options.size.1, seed, |boids| {
+        if let Some(preset) = options.preset {
+            preset.apply(boids);
+        }
+        (x.0.set)(boids, x.1);
+        if let Some((param, val)) = y {
+            (param.set)(boids, val);
+        }
+    });
+    let mut samples = vec![];
+    for step in 0..options.steps {
+        runner.step(options.dt);
+        if step >= options.warmup && (step - options.warmup).is_multiple_of(options.sample_every) {
+            samples.push(runner.metrics());
+        }
+    }
+    QUANTITIES
+        .iter()
+        .map(|(_, quantity)| Summary::of(samples.iter().map(quantity)).mean)
+        .collect()
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut x = None;
+    let mut y = None;
+    let mut preset = None;
+    let mut seeds = 3;
+    let mut steps = 2000;
+    let mut warmup = 500;
+    let mut sample_every = 10;
+    let mut dt = STEP_SECS;
+    let mut size = (1280.0, 720.0);
+    let mut out = None;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--x" => x = Some(parse_axis(&value()?)?),
+            "--y" => y = Some(parse_axis(&value()?)?),
+            "--preset" => {
+                let name = value()?;
+                let parsed = name
+                    .parse()
+                    .map_err(|_| format!("Unknown preset {}", name))?;
+                preset = Some(parsed);
+            }
+            "--seeds" => seeds = parse_number(&value()?)?,
+            "--steps" => steps = parse_number(&value()?)?,
+            "--warmup" => warmup = parse_number(&value()?)?,
+            "--sample-every" => sample_every = parse_number::<usize>(&value()?)?.max(1),
+            "--dt" => dt = parse_number(&value()?)?,
+            "--size" => {
+                let raw = value()?;
+                let mut parts = raw.splitn(2, 'x');
+                let width = parse_number(parts.next().unwrap_or(""))?;
+                let height = parse_number(parts.next().unwrap_or(""))?;
+                size = (width, height);
+            }
+            "--out" => out = Some(value()?),
+            "--help" | "-h" => return Err(String::from("Sweep parameters of the simulation")),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    Ok(Options {
+        x: x.ok_or("Missing --x")?,
+        y,
+        preset,
+        seeds,
+        steps,
+        warmup,
+        sample_every,
+        dt,
+        size,
+        out,
+    })
+}
+
+/// Parse `KEY=START:END:COUNT` into `COUNT` evenly spaced values.
+fn parse_axis(raw: &str) -> Result<Axis, String> {
+    let mut parts = raw.splitn(2, '=');
+    let key = parts.next().unwrap_or("");
+    let param = param_by_key(key).ok_or_else(|| format!("Unknown parameter {}", key))?;
+    let range: Vec<_> = parts.next().unwrap_or("").split(':').collect();
+    if range.len() != 3 {
+        return Err(format!("Expected {}=START:END:COUNT", key));
+    }
+    let start: f64 = parse_number(range[0])?;
+    let end: f64 = parse_number(range[1])?;
+    let count: usize = parse_number(range[2])?;
+    let values = match count {
+        0 => vec![],
+        1 => vec![start],
+        _ => (0..count)
+            .map(|idx| start + (end - start) * idx as f64 / (count - 1) as f64)
+            .map(|val| param.clamp(val))
+            .collect(),
+    };
+    Ok(Axis { param, values })
+}
+
+fn parse_number<T: std::str::FromStr>(raw: &str) -> Result<T, String> {
+    raw.parse().map_err(|_| format!("Invalid number {}", raw))
+}
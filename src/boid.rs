@@ -2,105 +2,145 @@ use lazy_static::lazy_static;
 use na::{Rotation2, Unit, Vector2};
 use nalgebra as na;
 use rand::{prelude::*, thread_rng};
-use yew::web_sys::{CanvasRenderingContext2d as Ctx, HtmlImageElement};
 
-use std::f64;
+use std::{collections::VecDeque, f64};
 
-use crate::{boids::Boids, Model, BUCKET_SIZE, DEFAULT_MAX_SPEED, QR_CODE_SIZE};
+use crate::{
+    boids::Boids,
+    draw::Draw,
+    profile::{Phase, Profiler, Sampler},
+    shape::{Appearance, Shape},
+    BUCKET_SIZE, DEFAULT_MAX_SPEED,
+};
 
 lazy_static! {
     pub static ref Y_AXIS: Unit<Vector2<f64>> = Vector2::y_axis();
-    pub static ref TRIANGLE_POINTS: [Vector2<f64>; 3] = {
-        // The length between the position and the tip of the boid
-        let l_to_tip = Vector2::new(0.0, 8.0);
-        // The length between the position and the bottom of the boid
-        let l_to_bottom = Vector2::new(0.0, -3.0);
-        // Half the length of the bottom itself
-        let bottom_half = Vector2::new(3.0, 0.0);
-        // The points of the triangle
-        let tri_a = l_to_bottom - bottom_half;
-        let tri_b = l_to_tip;
-        let tri_c = l_to_bottom + bottom_half;
-        [tri_a, tri_b, tri_c]
-    };
 }
 
+/// Radius of a boid drawn as dot, at scale one
+const DOT_RADIUS: f64 = 3.0;
+
 pub struct Boid {
     pub pos: Vector2<f64>,
     pub vel: Vector2<f64>,
     /// Partition id this boid is contained in
-    pub id: (usize, usize),
+    pub id: (isize, isize),
+    /// Id of the flock this boid belongs to, see [`Flocks`](crate::clusters::Flocks)
+    pub flock: Option<usize>,
+    /// Past positions, most recent first
+    pub trail: VecDeque<Vector2<f64>>,
+    /// Number of neighbours considered in the last step
+    pub neighbours: usize,
 }
 
 impl Boid {
     pub fn new(width: f64, height: f64) -> Self {
-        let mut rng = thread_rng();
+        Boid::with_rng(width, height, &mut thread_rng())
+    }
+
+    /// Create a random boid, drawing from the given random number generator.
+    pub fn with_rng(width: f64, height: f64, rng: &mut impl Rng) -> Self {
         // Use polar coordinates for the velocity generation
         let phi = rng.gen_range(0.0..(2.0 * f64::consts::PI));
         let vel = Vector2::new(phi.cos(), phi.sin());
         let pos = Vector2::new(rng.gen_range(0.0..width), rng.gen_range(0.0..height));
-        let id = (pos.x as usize / BUCKET_SIZE, pos.y as usize / BUCKET_SIZE);
+        let id = Boid::bucket_of(&pos);
         Boid {
             vel: vel * DEFAULT_MAX_SPEED / 2.0,
             pos,
             id,
+            flock: None,
+            trail: VecDeque::new(),
+            neighbours: 0,
         }
     }
 
-    pub fn update(curr_idx: usize, boids: &mut Boids, secs: f64) {
-        let relevant: Vec<_> = boids.get_weighted_others(curr_idx).collect();
-        let align_steer = boids.get_align_steer(&relevant);
-        let cohesion_steer = boids.get_cohesion_steer(&relevant, curr_idx);
-        let seperation_steer = boids.get_seperation_steer(&relevant, curr_idx);
-        let angst_steer = boids.get_angst_steer(curr_idx);
-        let return_steer = boids.get_return_steer(curr_idx);
-        // Accumulate steer
-        let mut steer: Vector2<f64> = na::zero();
-        steer += boids.align_factor * align_steer;
-        steer += boids.cohesion_factor * cohesion_steer;
-        steer += boids.seperation_factor * seperation_steer;
-        steer += boids.angst_factor * angst_steer;
-        // Limit the steer (acceleration)
-        steer = steer.cap_magnitude(boids.max_steer);
-        // Add return steer to force them back into the center
-        steer += return_steer;
-        // Limit the steer (acceleration)
-        steer = steer.cap_magnitude(boids.max_steer);
-        // Apply steer and limit the velocity
-        let vel = &mut boids.boids[curr_idx].vel;
-        *vel += steer;
-        *vel = vel.cap_magnitude(boids.max_speed);
-        // Apply velocity
-        let this = &mut boids.boids[curr_idx];
-        this.pos += this.vel * secs;
-        this.id = (
-            this.pos.x as usize / BUCKET_SIZE,
-            this.pos.y as usize / BUCKET_SIZE,
-        );
+    /// Id of the partition containing `pos`. Positions outside of the
+    /// world get ids outside of the grid instead of sharing the border buckets.
+    pub fn bucket_of(pos: &Vector2<f64>) -> (isize, isize) {
+        let size = BUCKET_SIZE as f64;
+        (
+            (pos.x / size).floor() as isize,
+            (pos.y / size).floor() as isize,
+        )
+    }
+
+    /// Move all boids by one step of `secs` seconds, one after another.
+    /// The phases are timed for a subset of the boids only.
+    pub fn update_all(boids: &mut Boids, secs: f64, profiler: &mut Profiler) {
+        let mut sampler = Sampler::new(profiler, boids.boids.len());
+        for idx in 0..boids.boids.len() {
+            sampler.select(idx);
+            Boid::update(idx, boids, secs, &mut sampler);
+        }
+    }
+
+    /// Move a single boid. Boids updated before it this step are
+    /// already at their new position.
+    pub fn update(curr_idx: usize, boids: &mut Boids, secs: f64, sampler: &mut Sampler) {
+        let relevant: Vec<_> =
+            sampler.measure(Phase::Neighbours, || boids.get_weighted_others(curr_idx));
+        let align_steer = sampler.measure(Phase::Align, || boids.get_align_steer(&relevant));
+        let cohesion_steer = sampler.measure(Phase::Cohesion, || {
+            boids.get_cohesion_steer(&relevant, curr_idx)
+        });
+        let seperation_steer = sampler.measure(Phase::Seperation, || {
+            boids.get_seperation_steer(&relevant, curr_idx)
+        });
+        let angst_steer = sampler.measure(Phase::Angst, || boids.get_angst_steer(curr_idx));
+        let return_steer = sampler.measure(Phase::Return, || boids.get_return_steer(curr_idx));
+        let neighbours = relevant.len();
+        sampler.measure(Phase::Integration, || {
+            // Accumulate steer
+            let mut steer: Vector2<f64> = na::zero();
+            steer += boids.align_factor * align_steer;
+            steer += boids.cohesion_factor * cohesion_steer;
+            steer += boids.seperation_factor * seperation_steer;
+            steer += boids.angst_factor * angst_steer;
+            // Limit the steer (acceleration)
+            steer = steer.cap_magnitude(boids.max_steer);
+            // Add return steer to force them back into the center
+            steer += return_steer;
+            // Limit the steer (acceleration)
+            steer = steer.cap_magnitude(boids.max_steer);
+            // Apply steer and limit the velocity
+            let max_speed = boids.max_speed;
+            let this = &mut boids.boids[curr_idx];
+            this.neighbours = neighbours;
+            this.vel += steer;
+            this.vel = this.vel.cap_magnitude(max_speed);
+            // Apply velocity
+            this.pos += this.vel * secs;
+            this.id = Boid::bucket_of(&this.pos);
+        });
     }
     /// Render the boid, this will not actually draw anything,
-    /// but create the necessary lines.
-    pub fn render(&self, model: &Model, ctx: &Ctx, qrcode: &HtmlImageElement) {
+    /// but create the necessary lines. Sprites are drawn as triangle,
+    /// see [`renderer::draw_boids`](crate::renderer::draw_boids) for drawing images
+    /// and boids with less detail.
+    pub fn render(&self, ctx: &impl Draw, appearance: &Appearance) {
         let (xx, yy) = (self.pos.x, self.pos.y);
-        if model.special_mode {
-            ctx.draw_image_with_html_image_element_and_dw_and_dh(
-                &qrcode,
-                xx - QR_CODE_SIZE / 2.0,
-                yy - QR_CODE_SIZE / 2.0,
-                QR_CODE_SIZE,
-                QR_CODE_SIZE,
-            )
-            .unwrap();
+        let scale = appearance.scale;
+        if appearance.shape == Shape::Dot {
+            let radius = DOT_RADIUS * scale;
+            ctx.move_to(xx + radius, yy);
+            ctx.arc(xx, yy, radius, 0.0, 2.0 * f64::consts::PI);
         } else {
             let rot = Rotation2::rotation_between(&Y_AXIS, &self.vel);
-            let tri_a_rot = rot.transform_vector(&TRIANGLE_POINTS[0]) + self.pos;
-            let tri_b_rot = rot.transform_vector(&TRIANGLE_POINTS[1]) + self.pos;
-            let tri_c_rot = rot.transform_vector(&TRIANGLE_POINTS[2]) + self.pos;
-
-            ctx.move_to(tri_a_rot.x, tri_a_rot.y);
-            ctx.line_to(tri_b_rot.x, tri_b_rot.y);
-            ctx.line_to(tri_c_rot.x, tri_c_rot.y);
-            ctx.line_to(tri_a_rot.x, tri_a_rot.y);
+            let outline = appearance.shape.outline();
+            for (idx, (x, y)) in outline.iter().chain(outline.first()).enumerate() {
+                let point = rot.transform_vector(&Vector2::new(*x, *y)) * scale + self.pos;
+                if idx == 0 {
+                    ctx.move_to(point.x, point.y);
+                } else {
+                    ctx.line_to(point.x, point.y);
+                }
+            }
         }
     }
+
+    /// Angle to turn an image facing up, so that it faces the heading.
+    pub fn sprite_angle(&self) -> f64 {
+        self.vel.y.atan2(self.vel.x) + f64::consts::FRAC_PI_2
+    }
 }
@@ -1,17 +1,25 @@
 use na::{Point2, Vector2};
 use nalgebra as na;
+use rand::{thread_rng, Rng};
 
-use std::f64;
+use std::{f64, iter};
 
 use crate::{
-    boid::Boid, BUCKET_SIZE, DEFAULT_ALIGN_FACTOR, DEFAULT_ALIGN_RADIUS, DEFAULT_ANGST_FACTOR,
-    DEFAULT_ANGST_RADIUS, DEFAULT_COHESION_FACTOR, DEFAULT_COHESION_RADIUS, DEFAULT_MAX_SPEED,
-    DEFAULT_MAX_STEER, DEFAULT_NR_OF_BOIDS, DEFAULT_SEPERATION_FACTOR, DEFAULT_SEPERATION_RADIUS,
-    RETURN_STEER_VAL, WALL_SIZE,
+    boid::Boid, clusters::Flocks, params, presets::Preset, BUCKET_SIZE, DEFAULT_ALIGN_FACTOR,
+    DEFAULT_ALIGN_RADIUS, DEFAULT_ANGST_FACTOR, DEFAULT_ANGST_RADIUS, DEFAULT_COHESION_FACTOR,
+    DEFAULT_COHESION_RADIUS, DEFAULT_MAX_SPEED, DEFAULT_MAX_STEER, DEFAULT_NR_OF_BOIDS,
+    DEFAULT_SEPERATION_FACTOR, DEFAULT_SEPERATION_RADIUS, RETURN_STEER_VAL, URL_VERSION, WALL_SIZE,
 };
 
 pub struct Boids {
     pub boids: Vec<Boid>,
+    pub flocks: Flocks,
+    /// Whether flocks are updated every step, see [`Boids::set_track_flocks`]
+    track_flocks: bool,
+    /// Boids temporarily removed to save time, they keep their state
+    pub suspended: Vec<Boid>,
+    /// Number of past positions remembered per boid, see [`Boid::trail`]
+    pub trail_length: usize,
     pub size: (f64, f64),
     pub align_radius_squared: f64,
     pub cohesion_radius_squared: f64,
@@ -19,6 +27,8 @@ pub struct Boids {
     pub angst_radius_squared: f64,
     pub debug_mode: bool,
     pub predators: Vec<Vector2<f64>>,
+    /// Temporary predators, one for every finger on the screen
+    pub touches: Vec<Vector2<f64>>,
     pub max_speed: f64,
     pub max_steer: f64,
     pub align_factor: f64,
@@ -47,12 +57,17 @@ impl Boids {
         let angst_factor = DEFAULT_ANGST_FACTOR;
         Boids {
             boids,
+            flocks: Flocks::new(),
+            track_flocks: false,
+            suspended: Vec::new(),
+            trail_length: 0,
             size,
             align_radius_squared,
             cohesion_radius_squared,
             seperation_radius_squared,
             angst_radius_squared,
             predators,
+            touches: vec![],
             debug_mode,
             max_speed,
             max_steer,
@@ -62,15 +77,38 @@ impl Boids {
             angst_factor,
         }
     }
+    pub fn track_flocks(&self) -> bool {
+        self.track_flocks
+    }
+
+    /// Start or stop detecting flocks every step. Flocks are detected
+    /// right away when started and forgotten when stopped.
+    pub fn set_track_flocks(&mut self, track: bool) {
+        if track && !self.track_flocks {
+            self.update_flocks();
+        } else if !track && self.track_flocks {
+            self.flocks.clear(&mut self.boids);
+        }
+        self.track_flocks = track;
+    }
+
+    pub fn update_flocks(&mut self) {
+        let cohesion_radius = self.cohesion_radius_squared.sqrt();
+        self.flocks.update(&mut self.boids, cohesion_radius);
+    }
+
     pub fn scatter(&mut self) {
-        for boid in &mut self.boids {
-            *boid = Boid::new(self.size.0, self.size.1)
+        self.scatter_with_rng(&mut thread_rng())
+    }
+    pub fn scatter_with_rng(&mut self, rng: &mut impl Rng) {
+        for boid in self.boids.iter_mut().chain(&mut self.suspended) {
+            *boid = Boid::with_rng(self.size.0, self.size.1, rng)
         }
     }
-    /// Get an iterator of relevant neighbours and their distance to the current boid.
+    /// Get the relevant neighbours and their squared distance to the current boid.
     /// A boid is not relevant if the distance to it is larger than the largest
     /// distance considered.
-    pub fn get_weighted_others(&self, curr_idx: usize) -> impl Iterator<Item = (&Boid, f64)> {
+    pub fn get_weighted_others(&self, curr_idx: usize) -> Vec<(&Boid, f64)> {
         let max_radius_squared = self
             .align_radius_squared
             .max(self.cohesion_radius_squared)
@@ -97,6 +135,7 @@ impl Boids {
                     None
                 }
             })
+            .collect()
     }
 
     pub fn get_return_steer(&self, curr_idx: usize) -> Vector2<f64> {
@@ -119,7 +158,7 @@ impl Boids {
     pub fn get_angst_steer(&self, curr_idx: usize) -> Vector2<f64> {
         let this_pos = Point2::origin() + self.boids[curr_idx].pos;
         let mut steer = na::zero();
-        for predator in &self.predators {
+        for predator in self.predators.iter().chain(&self.touches) {
             let pred_pos = Point2::origin() + predator;
             let dist_sq = na::distance_squared(&this_pos, &pred_pos);
             steer += if dist_sq > self.angst_radius_squared {
@@ -170,66 +209,77 @@ impl Boids {
         }
     }
 
-    pub fn to_url_suffix(&self) -> String {
-        let mut values = vec![
-            maybe(
-                "align-radius",
-                self.align_radius_squared.sqrt(),
-                DEFAULT_ALIGN_RADIUS,
-            ),
-            maybe(
-                "cohesion-radius",
-                self.cohesion_radius_squared.sqrt(),
-                DEFAULT_COHESION_RADIUS,
-            ),
-            maybe(
-                "seperation-radius",
-                self.seperation_radius_squared.sqrt(),
-                DEFAULT_SEPERATION_RADIUS,
-            ),
-            maybe(
-                "angst-radius",
-                self.angst_radius_squared.sqrt(),
-                DEFAULT_ANGST_RADIUS,
-            ),
-            maybe(
-                "nr-of-boids",
-                self.boids.len() as f64,
-                DEFAULT_NR_OF_BOIDS as f64,
-            ),
-            maybe("max-speed", self.max_speed, DEFAULT_MAX_SPEED),
-            maybe("max-steer", self.max_steer, DEFAULT_MAX_STEER),
-            maybe("align-factor", self.align_factor, DEFAULT_ALIGN_FACTOR),
-            maybe(
-                "cohesion-factor",
-                self.cohesion_factor,
-                DEFAULT_COHESION_FACTOR,
-            ),
-            maybe(
-                "seperation-factor",
-                self.seperation_factor,
-                DEFAULT_SEPERATION_FACTOR,
-            ),
-            maybe("angst-factor", self.angst_factor, DEFAULT_ANGST_FACTOR),
-        ];
-        values
-            .drain(..)
-            .flatten()
-            .map(|(name, val)| format!("{}={}", name, val))
-            .fold(String::from("?"), |concat, elem| concat + &elem + "&")
+    /// Url query naming `preset`, if any, starting with `&`.
+    fn preset_query(preset: Option<Preset>) -> String {
+        preset
+            .map(|preset| format!("&preset={}", preset.name()))
+            .unwrap_or_default()
+    }
+
+    /// Encode all parameters that differ from `preset` (or the defaults)
+    /// as human readable url query. The preset is named in the query, so
+    /// that what it places besides the parameters is restored as well.
+    pub fn to_url_suffix(&self, preset: Option<Preset>) -> String {
+        let values = params::ALL
+            .iter()
+            .filter(|param| !param.is_unchanged(self, preset))
+            .map(|param| format!("&{}={}", param.key, (param.get)(self)));
+        iter::once(format!("?v={}", URL_VERSION))
+            .chain(iter::once(Boids::preset_query(preset)))
+            .chain(values)
+            .collect()
     }
-}
 
-fn maybe(name: &str, val: f64, default: f64) -> Option<(&str, f64)> {
-    if (val - default).abs() > f64::EPSILON {
-        Some((name, val))
-    } else {
-        None
+    /// Encode all parameters that differ from `preset` (or the defaults)
+    /// as a single base64 blob, with the preset named in readable form.
+    ///
+    /// The blob contains the version, a bitmask of the parameters
+    /// contained (in the order of [`params::ALL`]) and their values as `f32`.
+    pub fn to_compact_url_suffix(&self, preset: Option<Preset>) -> String {
+        let mut mask: u16 = 0;
+        let mut bytes = vec![URL_VERSION, 0, 0];
+        for (idx, param) in params::ALL.iter().enumerate() {
+            if !param.is_unchanged(self, preset) {
+                mask |= 1 << idx;
+                bytes.extend_from_slice(&((param.get)(self) as f32).to_le_bytes());
+            }
+        }
+        bytes[1..3].copy_from_slice(&mask.to_le_bytes());
+        format!(
+            "?v={}{}&s={}",
+            URL_VERSION,
+            Boids::preset_query(preset),
+            base64::encode_config(&bytes, base64::URL_SAFE_NO_PAD)
+        )
     }
 }
 
-fn bucket_diff(this: &(usize, usize), other: &(usize, usize)) -> (usize, usize) {
-    let x = this.0.max(other.0) - this.0.min(other.0);
-    let y = this.1.max(other.1) - this.1.min(other.1);
-    (x, y)
+fn bucket_diff(this: &(isize, isize), other: &(isize, isize)) -> (usize, usize) {
+    (this.0.abs_diff(other.0), this.1.abs_diff(other.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Boids at the given positions in a 400 by 400 world.
+    fn boids_at(positions: &[(f64, f64)]) -> Boids {
+        let mut boids = Boids::new(400.0, 400.0);
+        boids.boids = positions
+            .iter()
+            .map(|(x, y)| {
+                let mut boid = Boid::new(400.0, 400.0);
+                boid.pos = Vector2::new(*x, *y);
+                boid.id = Boid::bucket_of(&boid.pos);
+                boid
+            })
+            .collect();
+        boids
+    }
+
+    #[test]
+    fn neighbours_are_all_in_range() {
+        let boids = boids_at(&[(100.0, 100.0), (101.0, 100.0), (390.0, 390.0)]);
+        assert_eq!(boids.get_weighted_others(0).len(), 1);
+    }
 }
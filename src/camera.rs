@@ -0,0 +1,156 @@
+use crate::{draw::Draw, MAX_ZOOM, MIN_ZOOM};
+use na::Vector2;
+use nalgebra as na;
+
+/// Maps the world the boids live in onto the screen.
+#[derive(Clone)]
+pub struct Camera {
+    /// World position shown in the top left corner of the view
+    pub offset: Vector2<f64>,
+    /// Screen pixels per world unit
+    pub zoom: f64,
+    /// Size of the view in screen pixels
+    pub view: (f64, f64),
+    /// Device pixels per screen pixel, see `window.devicePixelRatio`
+    pub pixel_ratio: f64,
+}
+
+impl Camera {
+    pub fn new(view: (f64, f64)) -> Self {
+        Camera {
+            offset: na::zero(),
+            zoom: 1.0,
+            view,
+            pixel_ratio: 1.0,
+        }
+    }
+
+    pub fn to_world(&self, screen: Vector2<f64>) -> Vector2<f64> {
+        self.offset + screen / self.zoom
+    }
+
+    pub fn to_screen(&self, world: Vector2<f64>) -> Vector2<f64> {
+        (world - self.offset) * self.zoom
+    }
+
+    /// Move the view by the given distance in screen pixels.
+    pub fn pan(&mut self, screen_delta: Vector2<f64>) {
+        self.offset -= screen_delta / self.zoom;
+    }
+
+    /// Multiply the zoom by `factor`, keeping the world position under `screen` in place.
+    pub fn zoom_at(&mut self, screen: Vector2<f64>, factor: f64) {
+        let world = self.to_world(screen);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset = world - screen / self.zoom;
+    }
+
+    /// Zoom and move, such that the whole world is visible and centered.
+    pub fn fit(&mut self, world: (f64, f64)) {
+        self.zoom = (self.view.0 / world.0)
+            .min(self.view.1 / world.1)
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+        let visible = Vector2::new(self.view.0, self.view.1) / self.zoom;
+        self.offset = (Vector2::new(world.0, world.1) - visible) / 2.0;
+    }
+
+    /// Size of the canvas backing store in device pixels.
+    pub fn backing_size(&self) -> (u32, u32) {
+        (
+            (self.view.0 * self.pixel_ratio).round() as u32,
+            (self.view.1 * self.pixel_ratio).round() as u32,
+        )
+    }
+
+    /// Draw in world coordinates from now on.
+    /// Lines stay one screen pixel wide, regardless of the zoom.
+    pub fn apply(&self, ctx: &impl Draw) {
+        let scale = self.zoom * self.pixel_ratio;
+        ctx.set_transform(
+            scale,
+            0.0,
+            0.0,
+            scale,
+            -self.offset.x * scale,
+            -self.offset.y * scale,
+        );
+        ctx.set_line_width(1.0 / self.zoom);
+    }
+
+    /// Draw in world coordinates moved to `pos` and rotated by `angle`
+    /// radians around it from now on.
+    pub fn apply_at(&self, ctx: &impl Draw, pos: Vector2<f64>, angle: f64) {
+        let scale = self.zoom * self.pixel_ratio;
+        let (sin, cos) = angle.sin_cos();
+        ctx.set_transform(
+            scale * cos,
+            scale * sin,
+            -scale * sin,
+            scale * cos,
+            (pos.x - self.offset.x) * scale,
+            (pos.y - self.offset.y) * scale,
+        );
+    }
+
+    /// Draw in screen coordinates from now on.
+    pub fn reset(&self, ctx: &impl Draw) {
+        let scale = self.pixel_ratio;
+        ctx.set_transform(scale, 0.0, 0.0, scale, 0.0, 0.0);
+        ctx.set_line_width(1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector2<f64>, b: Vector2<f64>) {
+        assert!((a - b).norm() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn screen_and_world_round_trip() {
+        let mut camera = Camera::new((800.0, 600.0));
+        camera.offset = Vector2::new(120.0, -40.0);
+        camera.zoom = 2.5;
+        let screen = Vector2::new(310.0, 45.0);
+        assert_close(camera.to_screen(camera.to_world(screen)), screen);
+        assert_close(camera.to_world(Vector2::zeros()), camera.offset);
+        assert_close(
+            camera.to_world(Vector2::new(25.0, 50.0)),
+            Vector2::new(130.0, -20.0),
+        );
+    }
+
+    #[test]
+    fn zoom_keeps_the_point_under_the_cursor() {
+        let mut camera = Camera::new((800.0, 600.0));
+        let screen = Vector2::new(200.0, 150.0);
+        let world = camera.to_world(screen);
+        camera.zoom_at(screen, 2.0);
+        assert_eq!(camera.zoom, 2.0);
+        assert_close(camera.to_world(screen), world);
+    }
+
+    #[test]
+    fn zoom_is_clamped() {
+        let mut camera = Camera::new((800.0, 600.0));
+        camera.zoom_at(Vector2::zeros(), 1e9);
+        assert_eq!(camera.zoom, MAX_ZOOM);
+        camera.zoom_at(Vector2::zeros(), 1e-9);
+        assert_eq!(camera.zoom, MIN_ZOOM);
+    }
+
+    #[test]
+    fn fit_centers_the_world() {
+        let mut camera = Camera::new((800.0, 600.0));
+        camera.fit((1600.0, 600.0));
+        assert_eq!(camera.zoom, 0.5);
+        // The world is 300 pixels high on screen, centered vertically
+        assert_close(camera.to_screen(Vector2::zeros()), Vector2::new(0.0, 150.0));
+        assert_close(
+            camera.to_screen(Vector2::new(1600.0, 600.0)),
+            Vector2::new(800.0, 450.0),
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{MAX_STEPS_PER_FRAME, SIMULATION_STEP};
+
+/// Turns the time passing between animation frames into a number of
+/// simulation steps of fixed length, independent of the frame rate.
+pub struct StepClock {
+    /// Simulation time in milliseconds that has not been stepped yet
+    unsimulated: f64,
+    /// Factor applied to the time passing between frames before it is simulated
+    pub time_scale: f64,
+}
+
+impl StepClock {
+    pub fn new() -> Self {
+        StepClock {
+            unsimulated: 0.0,
+            time_scale: 1.0,
+        }
+    }
+
+    /// Add `millis` of real time and return the number of steps to simulate.
+    ///
+    /// At most [`MAX_STEPS_PER_FRAME`] steps are taken per frame for every
+    /// started unit of the time scale, so that fast-forward keeps up with
+    /// slow frames. If we can't keep up, the time left over is dropped.
+    pub fn advance(&mut self, millis: f64) -> usize {
+        self.unsimulated += millis * self.time_scale;
+        let max_steps = MAX_STEPS_PER_FRAME * self.time_scale.ceil().max(1.0) as usize;
+        let steps = ((self.unsimulated / SIMULATION_STEP) as usize).min(max_steps);
+        self.unsimulated -= steps as f64 * SIMULATION_STEP;
+        self.unsimulated = self.unsimulated.min(SIMULATION_STEP);
+        steps
+    }
+
+    /// Forget the time that was not simulated yet.
+    pub fn reset(&mut self) {
+        self.unsimulated = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_steps_carry_over() {
+        let mut clock = StepClock::new();
+        assert_eq!(clock.advance(SIMULATION_STEP * 0.6), 0);
+        assert_eq!(clock.advance(SIMULATION_STEP * 0.6), 1);
+        assert_eq!(clock.advance(SIMULATION_STEP * 0.8), 1);
+    }
+
+    #[test]
+    fn time_scale_changes_the_number_of_steps() {
+        let mut clock = StepClock::new();
+        clock.time_scale = 0.5;
+        assert_eq!(clock.advance(SIMULATION_STEP * 4.0), 2);
+        clock.time_scale = 2.0;
+        assert_eq!(clock.advance(SIMULATION_STEP * 4.0), 8);
+    }
+
+    #[test]
+    fn fast_forward_keeps_up_with_slow_frames() {
+        let mut clock = StepClock::new();
+        clock.time_scale = 4.0;
+        // A frame at 30fps
+        let frame = 1000.0 / 30.0;
+        let steps: usize = (0..30).map(|_| clock.advance(frame)).sum();
+        assert_eq!(steps, 400);
+    }
+
+    #[test]
+    fn slow_frames_drop_time() {
+        let mut clock = StepClock::new();
+        assert_eq!(clock.advance(SIMULATION_STEP * 100.0), MAX_STEPS_PER_FRAME);
+        assert_eq!(clock.advance(0.0), 1);
+        assert_eq!(clock.advance(0.0), 0);
+    }
+
+    #[test]
+    fn reset_forgets_partial_steps() {
+        let mut clock = StepClock::new();
+        clock.advance(SIMULATION_STEP * 0.9);
+        clock.reset();
+        assert_eq!(clock.advance(SIMULATION_STEP * 0.5), 0);
+    }
+}
@@ -0,0 +1,226 @@
+use na::Point2;
+use nalgebra as na;
+
+use std::{cmp::Reverse, collections::HashMap};
+
+use crate::{boid::Boid, grid::Grid, FLOCK_COLORS, FLOCK_HISTOGRAM_BINS};
+
+/// Groups of boids connected by neighbours within the cohesion radius.
+///
+/// Every boid carries the id of the flock it belongs to. Ids are kept
+/// stable between steps by handing each new group the id most of its
+/// members had before.
+pub struct Flocks {
+    /// Size of every flock by id
+    pub sizes: HashMap<usize, usize>,
+    next_id: usize,
+}
+
+impl Flocks {
+    pub fn new() -> Self {
+        Flocks {
+            sizes: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    /// Number of flocks with more than one member, leaving out lone boids.
+    pub fn group_count(&self) -> usize {
+        self.sizes.values().filter(|size| **size > 1).count()
+    }
+
+    /// Size of the flock with the given id, zero if unknown.
+    pub fn size_of(&self, flock: Option<usize>) -> usize {
+        flock
+            .and_then(|flock| self.sizes.get(&flock))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Index into [`FLOCK_COLORS`] for members of the given flock.
+    /// Boids without any neighbours have none and keep the boid colour.
+    pub fn color_of(&self, flock: Option<usize>) -> Option<usize> {
+        match flock {
+            Some(flock) if self.size_of(Some(flock)) > 1 => Some(flock % FLOCK_COLORS.len()),
+            _ => None,
+        }
+    }
+
+    /// Number of flocks by size. Bin `i` counts flocks with `2^i` to
+    /// `2^(i+1) - 1` members, the last bin includes all larger flocks.
+    pub fn size_histogram(&self) -> [usize; FLOCK_HISTOGRAM_BINS] {
+        let mut histogram = [0; FLOCK_HISTOGRAM_BINS];
+        for size in self.sizes.values() {
+            let bin = (usize::BITS - size.leading_zeros()).saturating_sub(1) as usize;
+            histogram[bin.min(FLOCK_HISTOGRAM_BINS - 1)] += 1;
+        }
+        histogram
+    }
+
+    /// Forget all flocks, boids belong to none afterwards.
+    pub fn clear(&mut self, boids: &mut [Boid]) {
+        self.sizes.clear();
+        for boid in boids {
+            boid.flock = None;
+        }
+    }
+
+    /// Recompute the flocks and update the flock id of every boid.
+    pub fn update(&mut self, boids: &mut [Boid], radius: f64) {
+        let positions: Vec<_> = boids
+            .iter()
+            .map(|boid| Point2::origin() + boid.pos)
+            .collect();
+        let mut sets = DisjointSets::new(positions.len());
+        if radius > f64::EPSILON {
+            let grid = Grid::new(&positions, radius);
+            let radius_squared = radius.powf(2.0);
+            for idx in 0..positions.len() {
+                let center = grid.cell_of(idx);
+                let neighbours = grid.ring(center, 0).chain(grid.ring(center, 1));
+                for other in neighbours.filter(|other| *other > idx) {
+                    if na::distance_squared(&positions[idx], &positions[other]) <= radius_squared {
+                        sets.union(idx, other);
+                    }
+                }
+            }
+        }
+        let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+        for idx in 0..boids.len() {
+            groups.entry(sets.find(idx)).or_default().push(idx);
+        }
+        // Larger groups get the first pick of the old ids
+        let mut groups: Vec<_> = groups.into_values().collect();
+        groups.sort_by_key(|members| Reverse(members.len()));
+        let mut sizes = HashMap::with_capacity(groups.len());
+        for members in groups {
+            let mut votes: HashMap<usize, usize> = HashMap::new();
+            for flock in members.iter().filter_map(|idx| boids[*idx].flock) {
+                *votes.entry(flock).or_default() += 1;
+            }
+            let id = votes
+                .into_iter()
+                .filter(|(flock, _)| !sizes.contains_key(flock))
+                .max_by_key(|(flock, count)| (*count, Reverse(*flock)))
+                .map(|(flock, _)| flock)
+                .unwrap_or_else(|| {
+                    self.next_id += 1;
+                    self.next_id - 1
+                });
+            sizes.insert(id, members.len());
+            for idx in members {
+                boids[idx].flock = Some(id);
+            }
+        }
+        self.sizes = sizes;
+    }
+}
+
+/// Union-find over the indices `0..len`.
+struct DisjointSets {
+    parents: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(len: usize) -> Self {
+        DisjointSets {
+            parents: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, idx: usize) -> usize {
+        let mut root = idx;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        // Compress the path
+        let mut curr = idx;
+        while self.parents[curr] != root {
+            let next = self.parents[curr];
+            self.parents[curr] = root;
+            curr = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        if root_a != root_b {
+            self.parents[root_b] = root_a;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::Vector2;
+
+    /// Boids in a row at the given x coordinates, all at the same height.
+    fn boids_at(xs: &[f64]) -> Vec<Boid> {
+        xs.iter()
+            .map(|x| {
+                let mut boid = Boid::new(1000.0, 1000.0);
+                boid.pos = Vector2::new(*x, 500.0);
+                boid
+            })
+            .collect()
+    }
+
+    #[test]
+    fn groups_boids_within_the_radius() {
+        let mut boids = boids_at(&[0.0, 10.0, 20.0, 500.0, 510.0, 900.0]);
+        let mut flocks = Flocks::new();
+        flocks.update(&mut boids, 15.0);
+        assert_eq!(flocks.count(), 3);
+        assert_eq!(flocks.group_count(), 2);
+        assert_eq!(boids[0].flock, boids[2].flock);
+        assert_eq!(boids[3].flock, boids[4].flock);
+        assert_ne!(boids[0].flock, boids[3].flock);
+        assert_eq!(flocks.size_of(boids[0].flock), 3);
+        assert_eq!(flocks.color_of(boids[5].flock), None);
+    }
+
+    #[test]
+    fn ids_are_stable_when_flocks_move() {
+        let mut boids = boids_at(&[0.0, 10.0, 20.0, 500.0, 510.0]);
+        let mut flocks = Flocks::new();
+        flocks.update(&mut boids, 15.0);
+        let before: Vec<_> = boids.iter().map(|boid| boid.flock).collect();
+        for boid in &mut boids {
+            boid.pos.x += 100.0;
+        }
+        flocks.update(&mut boids, 15.0);
+        let after: Vec<_> = boids.iter().map(|boid| boid.flock).collect();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn merged_flock_keeps_the_id_of_the_larger_one() {
+        let mut boids = boids_at(&[0.0, 10.0, 20.0, 100.0, 110.0]);
+        let mut flocks = Flocks::new();
+        flocks.update(&mut boids, 15.0);
+        let larger = boids[0].flock;
+        boids[3].pos.x = 30.0;
+        boids[4].pos.x = 40.0;
+        flocks.update(&mut boids, 15.0);
+        assert_eq!(flocks.count(), 1);
+        assert!(boids.iter().all(|boid| boid.flock == larger));
+    }
+
+    #[test]
+    fn split_flock_gives_the_smaller_part_a_new_id() {
+        let mut boids = boids_at(&[0.0, 10.0, 20.0, 30.0, 40.0]);
+        let mut flocks = Flocks::new();
+        flocks.update(&mut boids, 15.0);
+        let id = boids[0].flock;
+        boids[4].pos.x = 300.0;
+        flocks.update(&mut boids, 15.0);
+        assert_eq!(boids[0].flock, id);
+        assert_ne!(boids[4].flock, id);
+    }
+}
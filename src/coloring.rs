@@ -0,0 +1,210 @@
+use na::Point2;
+use nalgebra as na;
+
+use std::{f64, iter};
+
+use crate::{
+    boids::Boids, draw::Draw, grid::Grid, theme::Theme, DENSITY_RADIUS, FLOCK_COLORS,
+    HEADING_COLORS, LEGEND_SWATCH_SIZE, QUANTITY_COLORS,
+};
+
+named_enum! {
+    /// The quantity boids are coloured by.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ColorMode {
+        /// Every boid in the boid colour of the theme
+        Uniform => ("uniform", "Single Colour"),
+        /// The flock a boid belongs to, see [`Flocks`](crate::clusters::Flocks)
+        Flock => ("flock", "Flock"),
+        Speed => ("speed", "Speed"),
+        /// Direction of the velocity, on a cyclic palette
+        Heading => ("heading", "Heading"),
+        /// Boids per 100x100 pixels within [`DENSITY_RADIUS`]
+        Density => ("density", "Local Density"),
+        /// Number of boids considered when steering in the last step
+        Neighbours => ("neighbours", "Neighbours"),
+        /// Steer caused by the predators, relative to the maximum steer
+        Stress => ("stress", "Stress"),
+    }
+}
+
+impl ColorMode {
+    /// The mode following this one, wrapping around at the end.
+    pub fn next(&self) -> ColorMode {
+        let idx = ColorMode::ALL
+            .iter()
+            .position(|mode| mode == self)
+            .unwrap_or(0);
+        ColorMode::ALL[(idx + 1) % ColorMode::ALL.len()]
+    }
+
+    /// All colours boids can get in this mode.
+    pub fn palette(&self, theme: &Theme) -> Vec<String> {
+        let palette: &[&str] = match self {
+            ColorMode::Uniform => return vec![theme.boid.clone()],
+            ColorMode::Flock => {
+                return iter::once(theme.boid.clone())
+                    .chain(FLOCK_COLORS.iter().map(|color| color.to_string()))
+                    .collect()
+            }
+            ColorMode::Heading => &HEADING_COLORS,
+            _ => &QUANTITY_COLORS,
+        };
+        palette.iter().map(|color| color.to_string()).collect()
+    }
+
+    /// The value of the quantity for every boid and the range mapped onto the palette.
+    fn values(&self, boids: &Boids) -> (Vec<f64>, (f64, f64)) {
+        let values: Vec<_> = match self {
+            ColorMode::Uniform | ColorMode::Flock => return (vec![], (0.0, 0.0)),
+            ColorMode::Speed => boids.boids.iter().map(|boid| boid.vel.norm()).collect(),
+            ColorMode::Heading => boids
+                .boids
+                .iter()
+                // Clockwise from north, like a compass
+                .map(|boid| {
+                    f64::atan2(boid.vel.x, -boid.vel.y)
+                        .to_degrees()
+                        .rem_euclid(360.0)
+                })
+                .collect(),
+            ColorMode::Density => density(boids),
+            ColorMode::Neighbours => boids
+                .boids
+                .iter()
+                .map(|boid| boid.neighbours as f64)
+                .collect(),
+            ColorMode::Stress => (0..boids.boids.len())
+                .map(|idx| {
+                    let steer = boids.angst_factor * boids.get_angst_steer(idx).norm();
+                    steer.min(boids.max_steer) / boids.max_steer
+                })
+                .collect(),
+        };
+        let range = match self {
+            ColorMode::Speed => (0.0, boids.max_speed),
+            ColorMode::Heading => (0.0, 360.0),
+            ColorMode::Stress => (0.0, 1.0),
+            // Unbounded quantities are scaled to the largest current value
+            _ => (0.0, values.iter().copied().fold(1.0, f64::max)),
+        };
+        (values, range)
+    }
+}
+
+/// The colour of every boid for one frame.
+pub struct Coloring {
+    pub mode: ColorMode,
+    /// All colours used, see [`ColorMode::palette`]
+    pub palette: Vec<String>,
+    /// Index into the palette for every boid, in the order of [`Boids::boids`]
+    pub colors: Vec<usize>,
+    /// Values mapped onto the first and the last colour of the palette
+    pub range: (f64, f64),
+}
+
+impl Coloring {
+    pub fn new(mode: ColorMode, boids: &Boids, theme: &Theme) -> Self {
+        let palette = mode.palette(theme);
+        let (values, range) = mode.values(boids);
+        let colors = match mode {
+            ColorMode::Uniform => vec![0; boids.boids.len()],
+            // The boid colour comes first, the flock colours follow
+            ColorMode::Flock => boids
+                .boids
+                .iter()
+                .map(|boid| boids.flocks.color_of(boid.flock).map_or(0, |idx| idx + 1))
+                .collect(),
+            _ => {
+                let span = (range.1 - range.0).max(f64::EPSILON);
+                values
+                    .iter()
+                    .map(|val| {
+                        let bin = ((val - range.0) / span * palette.len() as f64) as usize;
+                        // Only the heading wraps around, everything else saturates
+                        if mode == ColorMode::Heading {
+                            bin % palette.len()
+                        } else {
+                            bin.min(palette.len() - 1)
+                        }
+                    })
+                    .collect()
+            }
+        };
+        Coloring {
+            mode,
+            palette,
+            colors,
+            range,
+        }
+    }
+}
+
+/// Number of boids per 100x100 pixels in a circle around every boid.
+fn density(boids: &Boids) -> Vec<f64> {
+    let positions: Vec<_> = boids
+        .boids
+        .iter()
+        .map(|boid| Point2::new(boid.pos.x, boid.pos.y))
+        .collect();
+    let grid = Grid::new(&positions, DENSITY_RADIUS);
+    let area = f64::consts::PI * DENSITY_RADIUS.powf(2.0) / 10_000.0;
+    (0..positions.len())
+        .map(|idx| {
+            let center = grid.cell_of(idx);
+            let count = (0..=1)
+                .flat_map(|ring| grid.ring(center, ring))
+                .filter(|other| {
+                    na::distance_squared(&positions[idx], &positions[*other])
+                        <= DENSITY_RADIUS.powf(2.0)
+                })
+                .count();
+            count as f64 / area
+        })
+        .collect()
+}
+
+/// Draw the palette of `coloring` with its name and range in the bottom
+/// right corner of a view of the given size. Nothing is drawn for a single colour.
+pub fn draw_legend(ctx: &impl Draw, coloring: &Coloring, theme: &Theme, view: (f64, f64)) {
+    let mode = coloring.mode;
+    if mode == ColorMode::Uniform {
+        return;
+    }
+    let palette = &coloring.palette;
+    let width = palette.len() as f64 * LEGEND_SWATCH_SIZE;
+    let left = view.0 - width - 10.0;
+    let top = view.1 - LEGEND_SWATCH_SIZE - 30.0;
+    ctx.set_fill_style(&theme.statistics);
+    ctx.fill_text(mode.label(), left, top - 5.0);
+    for (idx, color) in palette.iter().enumerate() {
+        ctx.set_fill_style(color);
+        ctx.fill_rect(
+            left + idx as f64 * LEGEND_SWATCH_SIZE,
+            top,
+            LEGEND_SWATCH_SIZE,
+            LEGEND_SWATCH_SIZE,
+        );
+    }
+    let (low, high) = if mode == ColorMode::Flock {
+        (String::from("alone"), String::from("in a flock"))
+    } else {
+        (
+            format_value(coloring.range.0),
+            format_value(coloring.range.1),
+        )
+    };
+    ctx.set_fill_style(&theme.statistics);
+    let bottom = top + LEGEND_SWATCH_SIZE + 12.0;
+    ctx.fill_text(&low, left, bottom);
+    // Roughly right align the upper end, text is about 5 pixels per character
+    ctx.fill_text(&high, left + width - 5.0 * high.len() as f64, bottom);
+}
+
+fn format_value(val: f64) -> String {
+    if val.fract().abs() < f64::EPSILON {
+        format!("{}", val)
+    } else {
+        format!("{:.2}", val)
+    }
+}
@@ -1,33 +1,149 @@
-use wasm_bindgen::prelude::*;
-use yew::web_sys::CanvasRenderingContext2d;
-
 use std::f64;
 
 use crate::{
-    boid::Boid, boids::Boids, Model, ALIGN_RADIUS_COLOR, BORDER_COLOR, BUCKET_GRID_COLOR,
-    BUCKET_SIZE, COHESION_RADIUS_COLOR, PREDATOR_CLICK_RADIUS_SQUARED, PREDATOR_RADIUS_COLOR,
-    SEPERATION_RADIUS_COLOR, STATISTICS_COLOR, VELOCITY_COLOR, WALL_SIZE,
+    boid::Boid,
+    boids::Boids,
+    camera::Camera,
+    draw::Draw,
+    metrics::{Metrics, MetricsHistory},
+    profile::Phase,
+    theme::Theme,
+    Model, BUCKET_SIZE, FLOCK_HISTOGRAM_BINS, FRAME_TIME_HISTORY_LENGTH, METRICS_CHART_HEIGHT,
+    METRICS_CHART_WIDTH, METRICS_HISTORY_LENGTH, NN_HISTOGRAM_BINS, NN_HISTOGRAM_BIN_SIZE,
+    PREDATOR_CLICK_RADIUS_SQUARED, STEER_VECTOR_LENGTH, WALL_SIZE,
 };
 
-type Ctx = CanvasRenderingContext2d;
+/// Label, quantity and optional fixed range of a metrics chart
+type Chart = (&'static str, fn(&Metrics) -> f64, Option<(f64, f64)>);
 
-pub fn render_debug_info(ctx: &Ctx, model: &Model) {
+/// Draw the debug overlay. Expects the transform of `camera` to be applied.
+pub fn render_debug_info(ctx: &impl Draw, model: &Model, camera: &Camera) {
     let boids = &model.boids;
-    if let Some(first) = &boids.boids.first() {
-        draw_align_radius(ctx, boids, first);
-        draw_cohesion_radius(ctx, boids, first);
-        draw_seperation_radius(ctx, boids, first);
-        draw_velocity(ctx, first);
+    let theme = &model.theme;
+    // An inspected boid shows its radii and velocity itself
+    let first = boids.boids.first().filter(|_| model.inspected().is_none());
+    if let Some(first) = first {
+        draw_align_radius(ctx, boids, first, theme);
+        draw_cohesion_radius(ctx, boids, first, theme);
+        draw_seperation_radius(ctx, boids, first, theme);
+        draw_velocity(ctx, first, theme);
     }
-    draw_predator_radius(ctx, boids);
-    draw_border(ctx, boids);
-    draw_bucket_grid(ctx, boids);
+    draw_predator_radius(ctx, boids, theme);
+    draw_border(ctx, boids, theme);
+    draw_bucket_grid(ctx, boids, theme);
+    // Everything below is drawn in screen coordinates
+    camera.reset(ctx);
     draw_statistics(ctx, model);
+    draw_metrics(ctx, model);
+    draw_profile(ctx, model);
 }
 
-fn draw_align_radius(ctx: &Ctx, boids: &Boids, first: &Boid) {
+/// Highlight the boid at `idx` and draw its radii, links to its neighbours,
+/// its steering components and a readout of its state.
+/// Expects the transform of `camera` to be applied.
+pub fn render_inspection(ctx: &impl Draw, model: &Model, camera: &Camera, idx: usize) {
+    let boids = &model.boids;
+    let theme = &model.theme;
+    let boid = &boids.boids[idx];
+    let neighbours = boids.get_weighted_others(idx);
+    let components = [
+        (
+            "Align",
+            &theme.align_radius,
+            boids.align_factor * boids.get_align_steer(&neighbours),
+        ),
+        (
+            "Cohesion",
+            &theme.cohesion_radius,
+            boids.cohesion_factor * boids.get_cohesion_steer(&neighbours, idx),
+        ),
+        (
+            "Seperation",
+            &theme.seperation_radius,
+            boids.seperation_factor * boids.get_seperation_steer(&neighbours, idx),
+        ),
+        (
+            "Angst",
+            &theme.angst_steer,
+            boids.angst_factor * boids.get_angst_steer(idx),
+        ),
+        ("Return", &theme.return_steer, boids.get_return_steer(idx)),
+    ];
     ctx.begin_path();
-    ctx.set_stroke_style(&JsValue::from_str(ALIGN_RADIUS_COLOR));
+    ctx.set_stroke_style(&theme.metrics);
+    for (neighbour, _) in &neighbours {
+        ctx.move_to(boid.pos.x, boid.pos.y);
+        ctx.line_to(neighbour.pos.x, neighbour.pos.y);
+    }
+    ctx.stroke();
+    draw_align_radius(ctx, boids, boid, theme);
+    draw_cohesion_radius(ctx, boids, boid, theme);
+    draw_seperation_radius(ctx, boids, boid, theme);
+    draw_velocity(ctx, boid, theme);
+    for (_, color, steer) in &components {
+        let end = boid.pos + steer / boids.max_steer * STEER_VECTOR_LENGTH;
+        ctx.begin_path();
+        ctx.set_stroke_style(color);
+        ctx.move_to(boid.pos.x, boid.pos.y);
+        ctx.line_to(end.x, end.y);
+        ctx.stroke();
+    }
+    // Thick ring, that stays the same size on screen
+    let radius = 12.0 / camera.zoom;
+    ctx.begin_path();
+    ctx.set_stroke_style(&theme.statistics);
+    ctx.set_line_width(2.0 / camera.zoom);
+    ctx.move_to(boid.pos.x + radius, boid.pos.y);
+    ctx.arc(boid.pos.x, boid.pos.y, radius, 0.0, 2.0 * f64::consts::PI);
+    ctx.stroke();
+    // Everything below is drawn in screen coordinates
+    camera.reset(ctx);
+    let heading = boid.vel.x.atan2(-boid.vel.y).to_degrees().rem_euclid(360.0);
+    let mut lines = vec![
+        (format!("Boid {}", idx), &theme.statistics),
+        (
+            format!("Position: {:.0}, {:.0}", boid.pos.x, boid.pos.y),
+            &theme.statistics,
+        ),
+        (
+            format!("Speed: {:.1} of {:.0}", boid.vel.norm(), boids.max_speed),
+            &theme.statistics,
+        ),
+        (format!("Heading: {:.0}°", heading), &theme.statistics),
+        (
+            format!("Neighbours: {}", neighbours.len()),
+            &theme.statistics,
+        ),
+        (
+            match boid.flock {
+                Some(flock) => format!("Flock: {}", flock),
+                None => String::from("Flock: none"),
+            },
+            &theme.statistics,
+        ),
+        (
+            format!("Bucket: {}, {}", boid.id.0, boid.id.1),
+            &theme.statistics,
+        ),
+    ];
+    for (label, color, steer) in &components {
+        lines.push((format!("{} Steer: {:.2}", label, steer.norm()), color));
+    }
+    let line = 14.0;
+    let (left, top) = (model.camera.view.0 / 2.0 - 90.0, 10.0);
+    ctx.set_fill_style(&theme.background);
+    ctx.set_global_alpha(0.8);
+    ctx.fill_rect(left - 5.0, top, 190.0, (lines.len() as f64 + 0.5) * line);
+    ctx.set_global_alpha(1.0);
+    for (nr, (text, color)) in lines.iter().enumerate() {
+        ctx.set_fill_style(color);
+        ctx.fill_text(text, left, top + (nr as f64 + 1.0) * line);
+    }
+}
+
+fn draw_align_radius(ctx: &impl Draw, boids: &Boids, first: &Boid, theme: &Theme) {
+    ctx.begin_path();
+    ctx.set_stroke_style(&theme.align_radius);
     let align_radius = boids.align_radius_squared.sqrt();
     ctx.move_to(first.pos.x + align_radius, first.pos.y);
     ctx.arc(
@@ -36,14 +152,13 @@ fn draw_align_radius(ctx: &Ctx, boids: &Boids, first: &Boid) {
         align_radius,
         0.0,
         2.0 * f64::consts::PI,
-    )
-    .expect("Failed to draw boid align radius");
+    );
     ctx.stroke();
 }
 
-fn draw_cohesion_radius(ctx: &Ctx, boids: &Boids, first: &Boid) {
+fn draw_cohesion_radius(ctx: &impl Draw, boids: &Boids, first: &Boid, theme: &Theme) {
     ctx.begin_path();
-    ctx.set_stroke_style(&JsValue::from_str(COHESION_RADIUS_COLOR));
+    ctx.set_stroke_style(&theme.cohesion_radius);
     let cohesion_radius = boids.cohesion_radius_squared.sqrt();
     ctx.move_to(first.pos.x + cohesion_radius, first.pos.y);
     ctx.arc(
@@ -52,14 +167,13 @@ fn draw_cohesion_radius(ctx: &Ctx, boids: &Boids, first: &Boid) {
         cohesion_radius,
         0.0,
         2.0 * f64::consts::PI,
-    )
-    .expect("Failed to draw boid cohesion radius");
+    );
     ctx.stroke();
 }
 
-fn draw_seperation_radius(ctx: &Ctx, boids: &Boids, first: &Boid) {
+fn draw_seperation_radius(ctx: &impl Draw, boids: &Boids, first: &Boid, theme: &Theme) {
     ctx.begin_path();
-    ctx.set_stroke_style(&JsValue::from_str(SEPERATION_RADIUS_COLOR));
+    ctx.set_stroke_style(&theme.seperation_radius);
     let seperation_radius = boids.seperation_radius_squared.sqrt();
     ctx.move_to(first.pos.x + seperation_radius, first.pos.y);
     ctx.arc(
@@ -68,23 +182,22 @@ fn draw_seperation_radius(ctx: &Ctx, boids: &Boids, first: &Boid) {
         seperation_radius,
         0.0,
         2.0 * f64::consts::PI,
-    )
-    .expect("Failed to draw boid seperation radius");
+    );
     ctx.stroke();
 }
 
-fn draw_velocity(ctx: &Ctx, first: &Boid) {
+fn draw_velocity(ctx: &impl Draw, first: &Boid, theme: &Theme) {
     ctx.begin_path();
-    ctx.set_stroke_style(&JsValue::from_str(VELOCITY_COLOR));
+    ctx.set_stroke_style(&theme.velocity);
     ctx.move_to(first.pos.x, first.pos.y);
     ctx.line_to(first.pos.x + first.vel.x, first.pos.y + first.vel.y);
     ctx.stroke();
 }
 
-fn draw_predator_radius(ctx: &Ctx, boids: &Boids) {
-    for predator in &boids.predators {
+fn draw_predator_radius(ctx: &impl Draw, boids: &Boids, theme: &Theme) {
+    for predator in boids.predators.iter().chain(&boids.touches) {
         ctx.begin_path();
-        ctx.set_stroke_style(&JsValue::from_str(PREDATOR_RADIUS_COLOR));
+        ctx.set_stroke_style(&theme.predator);
         let angst_radius = boids.angst_radius_squared.sqrt();
         ctx.arc(
             predator.x,
@@ -92,11 +205,10 @@ fn draw_predator_radius(ctx: &Ctx, boids: &Boids) {
             angst_radius,
             0.0,
             2.0 * f64::consts::PI,
-        )
-        .expect("Failed to draw predator radius");
+        );
         ctx.stroke();
         ctx.begin_path();
-        ctx.set_fill_style(&JsValue::from_str(PREDATOR_RADIUS_COLOR));
+        ctx.set_fill_style(&theme.predator);
         let click_radius = PREDATOR_CLICK_RADIUS_SQUARED.sqrt();
         ctx.arc(
             predator.x,
@@ -104,15 +216,14 @@ fn draw_predator_radius(ctx: &Ctx, boids: &Boids) {
             click_radius,
             0.0,
             2.0 * f64::consts::PI,
-        )
-        .expect("Failed to draw predator");
+        );
         ctx.fill();
     }
 }
 
-fn draw_border(ctx: &Ctx, boids: &Boids) {
+fn draw_border(ctx: &impl Draw, boids: &Boids, theme: &Theme) {
     ctx.begin_path();
-    ctx.set_stroke_style(&JsValue::from_str(BORDER_COLOR));
+    ctx.set_stroke_style(&theme.border);
     ctx.move_to(WALL_SIZE, WALL_SIZE);
     ctx.line_to(boids.size.0 - WALL_SIZE, WALL_SIZE);
     ctx.line_to(boids.size.0 - WALL_SIZE, boids.size.1 - WALL_SIZE);
@@ -121,9 +232,9 @@ fn draw_border(ctx: &Ctx, boids: &Boids) {
     ctx.stroke();
 }
 
-fn draw_bucket_grid(ctx: &Ctx, boids: &Boids) {
+fn draw_bucket_grid(ctx: &impl Draw, boids: &Boids, theme: &Theme) {
     ctx.begin_path();
-    ctx.set_stroke_style(&JsValue::from_str(BUCKET_GRID_COLOR));
+    ctx.set_stroke_style(&theme.bucket_grid);
     for x in 0..=(boids.size.0 as usize / BUCKET_SIZE) {
         ctx.move_to((x * BUCKET_SIZE) as f64, 0.0);
         ctx.line_to((x * BUCKET_SIZE) as f64, boids.size.1);
@@ -135,12 +246,182 @@ fn draw_bucket_grid(ctx: &Ctx, boids: &Boids) {
     ctx.stroke();
 }
 
-fn draw_statistics(ctx: &Ctx, model: &Model) {
+fn draw_statistics(ctx: &impl Draw, model: &Model) {
+    let theme = &model.theme;
     ctx.begin_path();
-    ctx.set_fill_style(&JsValue::from_str(STATISTICS_COLOR));
+    ctx.set_fill_style(&theme.statistics);
     let fps = 1000.0 / model.last_time_passed;
     let text = format!("{:.2}", fps);
-    ctx.fill_text(&text, 10.0, model.boids.size.1 - 30.0)
-        .expect("Failed to draw fps");
+    ctx.fill_text(&text, 10.0, model.camera.view.1 - 30.0);
+    let text = format!("Flocks: {}", model.boids.flocks.group_count());
+    ctx.fill_text(&text, 10.0, model.camera.view.1 - 45.0);
+    let text = model.quality.describe(&model.boids);
+    ctx.fill_text(&text, 10.0, model.camera.view.1 - 15.0);
+    ctx.stroke();
+    draw_flock_histogram(ctx, &model.boids, theme, 10.0, model.camera.view.1 - 60.0);
+}
+
+/// Draw the number of flocks by size as bars above `bottom`.
+fn draw_flock_histogram(ctx: &impl Draw, boids: &Boids, theme: &Theme, left: f64, bottom: f64) {
+    let histogram = boids.flocks.size_histogram();
+    let highest = histogram.iter().max().copied().unwrap_or(0).max(1);
+    let bar_width = METRICS_CHART_WIDTH / FLOCK_HISTOGRAM_BINS as f64;
+    for (bin, count) in histogram.iter().enumerate() {
+        let height = 2.0 * METRICS_CHART_HEIGHT * *count as f64 / highest as f64;
+        let x = left + bin as f64 * bar_width;
+        ctx.set_fill_style(&theme.metrics);
+        ctx.fill_rect(x, bottom - height - 12.0, bar_width - 1.0, height);
+        ctx.set_fill_style(&theme.statistics);
+        ctx.fill_text(&format!("{}", 1 << bin), x, bottom);
+    }
+}
+
+fn draw_metrics(ctx: &impl Draw, model: &Model) {
+    let theme = &model.theme;
+    let history = &model.metrics;
+    let latest = match history.latest() {
+        Some(latest) => latest,
+        None => return,
+    };
+    let left = model.camera.view.0 - METRICS_CHART_WIDTH - 10.0;
+    let row = METRICS_CHART_HEIGHT + 15.0;
+    let charts: [Chart; 6] = [
+        ("Polarization", |m| m.polarization, Some((0.0, 1.0))),
+        ("Milling", |m| m.milling, Some((0.0, 1.0))),
+        ("Mean NN Distance", |m| m.mean_nn_distance, None),
+        ("Clusters", |m| m.clusters as f64, None),
+        ("Mean Speed", |m| m.mean_speed, None),
+        ("Density", |m| m.density, None),
+    ];
+    for (nr, (label, quantity, range)) in charts.iter().enumerate() {
+        let top = 10.0 + nr as f64 * row;
+        let text = format!("{}: {:.2}", label, quantity(latest));
+        ctx.set_fill_style(&theme.statistics);
+        ctx.fill_text(&text, left, top + 10.0);
+        draw_sparkline(ctx, history, theme, *quantity, *range, left, top + 12.0);
+    }
+    let top = 10.0 + charts.len() as f64 * row;
+    draw_nn_histogram(ctx, latest, theme, left, top);
+}
+
+/// Draw the history of a quantity as line, scaled to `range` or the
+/// smallest and largest value if none is given.
+fn draw_sparkline(
+    ctx: &impl Draw,
+    history: &MetricsHistory,
+    theme: &Theme,
+    quantity: fn(&Metrics) -> f64,
+    range: Option<(f64, f64)>,
+    left: f64,
+    top: f64,
+) {
+    let (min, max) = range.unwrap_or_else(|| {
+        history
+            .series(quantity)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), val| {
+                (min.min(val), max.max(val))
+            })
+    });
+    let span = if max - min > f64::EPSILON {
+        max - min
+    } else {
+        1.0
+    };
+    let step = METRICS_CHART_WIDTH / (METRICS_HISTORY_LENGTH - 1) as f64;
+    // Right align the line, so that it grows from the right
+    let offset = (METRICS_HISTORY_LENGTH - history.len()) as f64 * step;
+    ctx.begin_path();
+    ctx.set_stroke_style(&theme.metrics);
+    for (idx, val) in history.series(quantity).enumerate() {
+        let x = left + offset + idx as f64 * step;
+        let y = top + METRICS_CHART_HEIGHT * (1.0 - (val - min) / span);
+        if idx == 0 {
+            ctx.move_to(x, y);
+        } else {
+            ctx.line_to(x, y);
+        }
+    }
+    ctx.stroke();
+}
+
+fn draw_nn_histogram(ctx: &impl Draw, metrics: &Metrics, theme: &Theme, left: f64, top: f64) {
+    ctx.set_fill_style(&theme.statistics);
+    let text = format!(
+        "NN Distances (0-{}px)",
+        NN_HISTOGRAM_BIN_SIZE * NN_HISTOGRAM_BINS as f64
+    );
+    ctx.fill_text(&text, left, top + 10.0);
+    let highest = metrics
+        .nn_histogram
+        .iter()
+        .max()
+        .copied()
+        .unwrap_or(0)
+        .max(1);
+    let bar_width = METRICS_CHART_WIDTH / NN_HISTOGRAM_BINS as f64;
+    let bottom = top + 12.0 + 2.0 * METRICS_CHART_HEIGHT;
+    ctx.set_fill_style(&theme.metrics);
+    for (bin, count) in metrics.nn_histogram.iter().enumerate() {
+        let height = 2.0 * METRICS_CHART_HEIGHT * *count as f64 / highest as f64;
+        ctx.fill_rect(
+            left + bin as f64 * bar_width,
+            bottom - height,
+            bar_width - 1.0,
+            height,
+        );
+    }
+}
+
+/// Draw the rolling time per phase as stacked bar with a legend,
+/// and the duration of the recent frames as graph.
+fn draw_profile(ctx: &impl Draw, model: &Model) {
+    let profiler = &model.profiler;
+    let theme = &model.theme;
+    let left = 220.0;
+    let bottom = model.camera.view.1 - 30.0;
+    let total: f64 = Phase::ALL
+        .iter()
+        .map(|phase| profiler.average(*phase))
+        .sum();
+    let scale = if total > f64::EPSILON {
+        METRICS_CHART_WIDTH / total
+    } else {
+        0.0
+    };
+    let line = 12.0;
+    let top = bottom - (Phase::ALL.len() as f64 + 1.0) * line - 2.0 * METRICS_CHART_HEIGHT;
+    let mut x = left;
+    for (nr, phase) in Phase::ALL.iter().enumerate() {
+        let millis = profiler.average(*phase);
+        let color = if phase.is_simulation() {
+            &theme.simulation_profile
+        } else {
+            &theme.render_profile
+        };
+        ctx.set_fill_style(color);
+        ctx.fill_rect(x, top, millis * scale, METRICS_CHART_HEIGHT / 2.0);
+        x += millis * scale;
+        ctx.set_fill_style(&theme.statistics);
+        let text = format!("{}: {:.2}ms", phase.label(), millis);
+        let y = top + METRICS_CHART_HEIGHT / 2.0 + (nr as f64 + 1.0) * line;
+        ctx.fill_text(&text, left, y);
+    }
+    // Frame times, scaled to the slowest frame
+    let slowest = profiler.frame_times().fold(1.0, f64::max);
+    let step = METRICS_CHART_WIDTH / (FRAME_TIME_HISTORY_LENGTH - 1) as f64;
+    ctx.begin_path();
+    ctx.set_stroke_style(&theme.metrics);
+    for (idx, millis) in profiler.frame_times().enumerate() {
+        let x = left + idx as f64 * step;
+        let y = bottom - 2.0 * METRICS_CHART_HEIGHT * millis / slowest;
+        if idx == 0 {
+            ctx.move_to(x, y);
+        } else {
+            ctx.line_to(x, y);
+        }
+    }
     ctx.stroke();
+    ctx.set_fill_style(&theme.statistics);
+    let text = format!("Frame Time (max {:.1}ms)", slowest);
+    ctx.fill_text(&text, left, bottom + line);
 }
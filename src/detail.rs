@@ -0,0 +1,106 @@
+use crate::{BOID_LENGTH, LOD_DOT_BOIDS, LOD_DOT_SIZE, LOD_PIXEL_BOIDS, LOD_PIXEL_SIZE};
+
+named_enum! {
+    /// How much detail boids are drawn with.
+    ///
+    /// Dots and pixels are squares, which are much cheaper to draw
+    /// than rotated shapes and are batched into one path per colour.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Detail {
+        /// Pick one of the others by the number of boids and their size on screen
+        Auto => ("auto", "Automatic"),
+        /// The selected shape or sprite
+        Full => ("full", "Full"),
+        Dots => ("dots", "Dots"),
+        /// Single device pixels
+        Pixels => ("pixels", "Pixels"),
+    }
+}
+
+impl Detail {
+    /// The detail to draw `count` boids with, never [`Detail::Auto`].
+    /// `screen_scale` is the number of screen pixels per world unit times
+    /// the boid scale, `low_detail` is requested by the adaptive quality.
+    pub fn resolve(&self, count: usize, screen_scale: f64, low_detail: bool) -> Detail {
+        let size = BOID_LENGTH * screen_scale;
+        let detail = match self {
+            Detail::Auto if count > LOD_PIXEL_BOIDS || size < LOD_PIXEL_SIZE => Detail::Pixels,
+            Detail::Auto if count > LOD_DOT_BOIDS || size < LOD_DOT_SIZE => Detail::Dots,
+            Detail::Auto => Detail::Full,
+            detail => *detail,
+        };
+        if low_detail && detail == Detail::Full {
+            Detail::Dots
+        } else {
+            detail
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Screen scale at which a boid is exactly `size` pixels long
+    fn scale(size: f64) -> f64 {
+        size / BOID_LENGTH
+    }
+
+    #[test]
+    fn auto_follows_the_boid_count() {
+        let large = scale(LOD_DOT_SIZE * 2.0);
+        assert_eq!(
+            Detail::Auto.resolve(LOD_DOT_BOIDS, large, false),
+            Detail::Full
+        );
+        assert_eq!(
+            Detail::Auto.resolve(LOD_DOT_BOIDS + 1, large, false),
+            Detail::Dots
+        );
+        assert_eq!(
+            Detail::Auto.resolve(LOD_PIXEL_BOIDS, large, false),
+            Detail::Dots
+        );
+        assert_eq!(
+            Detail::Auto.resolve(LOD_PIXEL_BOIDS + 1, large, false),
+            Detail::Pixels
+        );
+    }
+
+    #[test]
+    fn auto_follows_the_size_on_screen() {
+        assert_eq!(
+            Detail::Auto.resolve(1, scale(LOD_DOT_SIZE), false),
+            Detail::Full
+        );
+        assert_eq!(
+            Detail::Auto.resolve(1, scale(LOD_DOT_SIZE * 0.99), false),
+            Detail::Dots
+        );
+        assert_eq!(
+            Detail::Auto.resolve(1, scale(LOD_PIXEL_SIZE), false),
+            Detail::Dots
+        );
+        assert_eq!(
+            Detail::Auto.resolve(1, scale(LOD_PIXEL_SIZE * 0.99), false),
+            Detail::Pixels
+        );
+    }
+
+    #[test]
+    fn low_detail_only_replaces_full() {
+        let large = scale(LOD_DOT_SIZE * 2.0);
+        assert_eq!(Detail::Auto.resolve(1, large, true), Detail::Dots);
+        assert_eq!(Detail::Full.resolve(1, large, true), Detail::Dots);
+        assert_eq!(Detail::Pixels.resolve(1, large, true), Detail::Pixels);
+        assert_eq!(
+            Detail::Full.resolve(LOD_PIXEL_BOIDS + 1, large, false),
+            Detail::Full
+        );
+    }
+
+    #[test]
+    fn thresholds_are_reachable() {
+        assert!(crate::params::NR_OF_BOIDS.max > LOD_PIXEL_BOIDS as f64);
+    }
+}
@@ -0,0 +1,149 @@
+use wasm_bindgen::JsValue;
+use yew::web_sys::{CanvasRenderingContext2d, HtmlImageElement};
+
+/// The drawing operations used to render boids and the debug overlay.
+///
+/// Mirrors the subset of `CanvasRenderingContext2d` in use, so that the same
+/// drawing code runs in the browser and with the native raster backend.
+/// Colours are css colours, see [`parse_color`] for what every backend understands.
+pub trait Draw {
+    type Image;
+
+    /// Replace the current transformation matrix, see `CanvasRenderingContext2d.setTransform`.
+    fn set_transform(&self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64);
+    fn set_fill_style(&self, color: &str);
+    fn set_stroke_style(&self, color: &str);
+    fn set_line_width(&self, width: f64);
+    /// Opacity applied to everything drawn afterwards, from 0 to 1.
+    fn set_global_alpha(&self, alpha: f64);
+    fn begin_path(&self);
+    fn move_to(&self, x: f64, y: f64);
+    fn line_to(&self, x: f64, y: f64);
+    /// Add a circular arc to the path, connected to the current point by a line.
+    fn arc(&self, x: f64, y: f64, radius: f64, start: f64, end: f64);
+    fn rect(&self, x: f64, y: f64, width: f64, height: f64);
+    fn fill(&self);
+    fn stroke(&self);
+    fn fill_rect(&self, x: f64, y: f64, width: f64, height: f64);
+    fn fill_text(&self, text: &str, x: f64, y: f64);
+    fn draw_image(&self, image: &Self::Image, x: f64, y: f64, width: f64, height: f64);
+}
+
+impl Draw for CanvasRenderingContext2d {
+    type Image = HtmlImageElement;
+
+    fn set_transform(&self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
+        CanvasRenderingContext2d::set_transform(self, a, b, c, d, e, f)
+            .expect("Failed to set transform");
+    }
+
+    fn set_fill_style(&self, color: &str) {
+        CanvasRenderingContext2d::set_fill_style(self, &JsValue::from_str(color));
+    }
+
+    fn set_stroke_style(&self, color: &str) {
+        CanvasRenderingContext2d::set_stroke_style(self, &JsValue::from_str(color));
+    }
+
+    fn set_line_width(&self, width: f64) {
+        CanvasRenderingContext2d::set_line_width(self, width);
+    }
+
+    fn set_global_alpha(&self, alpha: f64) {
+        CanvasRenderingContext2d::set_global_alpha(self, alpha);
+    }
+
+    fn begin_path(&self) {
+        CanvasRenderingContext2d::begin_path(self);
+    }
+
+    fn move_to(&self, x: f64, y: f64) {
+        CanvasRenderingContext2d::move_to(self, x, y);
+    }
+
+    fn line_to(&self, x: f64, y: f64) {
+        CanvasRenderingContext2d::line_to(self, x, y);
+    }
+
+    fn arc(&self, x: f64, y: f64, radius: f64, start: f64, end: f64) {
+        CanvasRenderingContext2d::arc(self, x, y, radius, start, end).expect("Failed to draw arc");
+    }
+
+    fn rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        CanvasRenderingContext2d::rect(self, x, y, width, height);
+    }
+
+    fn fill(&self) {
+        CanvasRenderingContext2d::fill(self);
+    }
+
+    fn stroke(&self) {
+        CanvasRenderingContext2d::stroke(self);
+    }
+
+    fn fill_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        CanvasRenderingContext2d::fill_rect(self, x, y, width, height);
+    }
+
+    fn fill_text(&self, text: &str, x: f64, y: f64) {
+        CanvasRenderingContext2d::fill_text(self, text, x, y).expect("Failed to draw text");
+    }
+
+    fn draw_image(&self, image: &HtmlImageElement, x: f64, y: f64, width: f64, height: f64) {
+        self.draw_image_with_html_image_element_and_dw_and_dh(image, x, y, width, height)
+            .expect("Failed to draw image");
+    }
+}
+
+/// Parse a css colour into its rgba components.
+///
+/// Only `#rrggbb`, `#rrggbbaa` and the few named colours used
+/// in this crate are supported, anything else is black.
+pub fn parse_color(color: &str) -> [u8; 4] {
+    match color {
+        "black" => return [0, 0, 0, 255],
+        "white" => return [255, 255, 255, 255],
+        "red" => return [255, 0, 0, 255],
+        "green" => return [0, 128, 0, 255],
+        "blue" => return [0, 0, 255, 255],
+        _ => {}
+    }
+    let channel = |idx: usize| {
+        color
+            .get(1 + 2 * idx..3 + 2 * idx)
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+    };
+    let alpha = if color.len() == 9 {
+        channel(3)
+    } else {
+        Some(255)
+    };
+    match (channel(0), channel(1), channel(2), alpha) {
+        (Some(r), Some(g), Some(b), Some(a)) if color.starts_with('#') => [r, g, b, a],
+        _ => [0, 0, 0, 255],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_colors() {
+        assert_eq!(parse_color("white"), [255, 255, 255, 255]);
+        assert_eq!(parse_color("green"), [0, 128, 0, 255]);
+    }
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(parse_color("#123abc"), [0x12, 0x3a, 0xbc, 255]);
+        assert_eq!(parse_color("#123ABC80"), [0x12, 0x3a, 0xbc, 0x80]);
+    }
+
+    #[test]
+    fn unsupported_colors_are_black() {
+        for color in &["", "#12345", "#12345g", "123abc", "rgb(1, 2, 3)", "purple"] {
+            assert_eq!(parse_color(color), [0, 0, 0, 255], "{}", color);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use na::Vector2;
+use nalgebra as na;
+
+use crate::{
+    boids::Boids, draw::Draw, theme::Theme, BUCKET_SIZE, HEATMAP_OPACITY, QUANTITY_COLORS,
+};
+
+/// The flock rasterised onto the buckets of the spatial partition,
+/// see [`Boid::bucket_of`](crate::boid::Boid::bucket_of).
+pub struct CellField {
+    columns: usize,
+    rows: usize,
+    /// Number of boids per cell, row by row
+    counts: Vec<usize>,
+    /// Mean velocity of the boids per cell
+    velocities: Vec<Vector2<f64>>,
+}
+
+impl CellField {
+    pub fn new(boids: &Boids) -> Self {
+        let columns = (boids.size.0 / BUCKET_SIZE as f64).ceil().max(1.0) as usize;
+        let rows = (boids.size.1 / BUCKET_SIZE as f64).ceil().max(1.0) as usize;
+        let mut counts = vec![0; columns * rows];
+        let mut velocities = vec![Vector2::zeros(); columns * rows];
+        for boid in &boids.boids {
+            // Boids beyond the walls count towards the outermost cells
+            let column = (boid.pos.x / BUCKET_SIZE as f64).max(0.0) as usize;
+            let row = (boid.pos.y / BUCKET_SIZE as f64).max(0.0) as usize;
+            let idx = row.min(rows - 1) * columns + column.min(columns - 1);
+            counts[idx] += 1;
+            velocities[idx] += boid.vel;
+        }
+        for (velocity, count) in velocities.iter_mut().zip(&counts) {
+            if *count > 0 {
+                *velocity /= *count as f64;
+            }
+        }
+        CellField {
+            columns,
+            rows,
+            counts,
+            velocities,
+        }
+    }
+
+    /// Cells with at least one boid, as top left corner, count and mean velocity.
+    fn occupied(&self) -> impl Iterator<Item = (Vector2<f64>, usize, Vector2<f64>)> + '_ {
+        let size = BUCKET_SIZE as f64;
+        (0..self.rows)
+            .flat_map(move |row| (0..self.columns).map(move |column| (row, column)))
+            .filter_map(move |(row, column)| {
+                let idx = row * self.columns + column;
+                let corner = Vector2::new(column as f64 * size, row as f64 * size);
+                Some((corner, self.counts[idx], self.velocities[idx]))
+                    .filter(|(_, count, _)| *count > 0)
+            })
+    }
+}
+
+/// Fill every occupied cell with a colour for the number of boids in it,
+/// relative to the busiest cell. Expects the world transform to be applied.
+pub fn draw_heatmap(ctx: &impl Draw, field: &CellField) {
+    let busiest = field.counts.iter().max().copied().unwrap_or(0).max(1);
+    let size = BUCKET_SIZE as f64;
+    let last = QUANTITY_COLORS.len() - 1;
+    ctx.set_global_alpha(HEATMAP_OPACITY);
+    for (corner, count, _) in field.occupied() {
+        let idx = count * last / busiest;
+        ctx.set_fill_style(QUANTITY_COLORS[idx]);
+        ctx.fill_rect(corner.x, corner.y, size, size);
+    }
+    ctx.set_global_alpha(1.0);
+}
+
+/// Draw the mean velocity of every occupied cell as arrow through its
+/// center, half a cell long at maximum speed. Expects the world transform
+/// to be applied. Nothing is drawn if the boids may not move at all.
+pub fn draw_velocity_field(ctx: &impl Draw, field: &CellField, boids: &Boids, theme: &Theme) {
+    if boids.max_speed <= 0.0 {
+        return;
+    }
+    let size = BUCKET_SIZE as f64;
+    ctx.begin_path();
+    ctx.set_stroke_style(&theme.velocity_field);
+    for (corner, _, velocity) in field.occupied() {
+        let arrow = velocity / boids.max_speed * size / 2.0;
+        let center = corner + Vector2::new(size, size) / 2.0;
+        let (tail, tip) = (center - arrow / 2.0, center + arrow / 2.0);
+        ctx.move_to(tail.x, tail.y);
+        ctx.line_to(tip.x, tip.y);
+        // Barbs at a right angle to each other
+        let back = -arrow * 0.3;
+        let side = Vector2::new(-back.y, back.x);
+        for barb in &[back + side, back - side] {
+            ctx.move_to(tip.x, tip.y);
+            ctx.line_to(tip.x + barb.x, tip.y + barb.y);
+        }
+    }
+    ctx.stroke();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svg::Svg;
+
+    /// Boids in a 200 by 100 world, four by two cells, at the given positions.
+    fn boids_at(positions: &[(f64, f64)]) -> Boids {
+        let mut boids = Boids::new(4.0 * BUCKET_SIZE as f64, 2.0 * BUCKET_SIZE as f64);
+        boids.boids.truncate(positions.len());
+        for (boid, (x, y)) in boids.boids.iter_mut().zip(positions) {
+            boid.pos = Vector2::new(*x, *y);
+        }
+        boids
+    }
+
+    #[test]
+    fn heatmap_counts_boids_per_cell() {
+        let size = BUCKET_SIZE as f64;
+        let boids = boids_at(&[
+            (10.0, 10.0),
+            (20.0, 20.0),
+            // On the boundary, in the next cell
+            (size, 10.0),
+            (3.0 * size + 10.0, size + 10.0),
+            // Beyond the walls, in the outermost cells
+            (-5.0, 10.0),
+            (1000.0, 1000.0),
+        ]);
+        let field = CellField::new(&boids);
+        assert_eq!(field.counts, vec![3, 1, 0, 0, 0, 0, 0, 2]);
+        let svg = Svg::new(boids.size.0, boids.size.1);
+        draw_heatmap(&svg, &field);
+        let svg = svg.finish();
+        // The busiest cell gets the last colour, the others are scaled to it
+        let last = QUANTITY_COLORS.len() - 1;
+        assert_eq!(svg.matches(QUANTITY_COLORS[last]).count(), 1, "{}", svg);
+        assert_eq!(svg.matches(QUANTITY_COLORS[last / 3]).count(), 1, "{}", svg);
+        assert_eq!(
+            svg.matches(QUANTITY_COLORS[2 * last / 3]).count(),
+            1,
+            "{}",
+            svg
+        );
+        assert_eq!(svg.matches("<rect").count(), 3, "{}", svg);
+    }
+
+    fn velocity_field_svg(boids: &Boids) -> String {
+        let svg = Svg::new(boids.size.0, boids.size.1);
+        draw_velocity_field(&svg, &CellField::new(boids), boids, &Theme::light());
+        svg.finish()
+    }
+
+    #[test]
+    fn velocity_field_draws_arrows() {
+        let boids = Boids::new(400.0, 400.0);
+        assert!(velocity_field_svg(&boids).contains("<path"));
+    }
+
+    #[test]
+    fn velocity_field_without_speed_is_empty() {
+        let mut boids = Boids::new(400.0, 400.0);
+        boids.max_speed = 0.0;
+        let svg = velocity_field_svg(&boids);
+        assert!(!svg.contains("NaN") && !svg.contains("inf"), "{}", svg);
+    }
+}
@@ -0,0 +1,80 @@
+use na::Point2;
+use nalgebra as na;
+
+use std::collections::HashMap;
+
+/// Uniform grid of point indices, used to find neighbours without
+/// comparing every pair of boids.
+pub struct Grid<'a> {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+    /// Smallest and largest occupied cell coordinates
+    bounds: ((i64, i64), (i64, i64)),
+    positions: &'a [Point2<f64>],
+}
+
+impl<'a> Grid<'a> {
+    pub fn new(positions: &'a [Point2<f64>], cell_size: f64) -> Self {
+        let mut cells: HashMap<_, Vec<_>> = HashMap::new();
+        let mut bounds = ((i64::MAX, i64::MAX), (i64::MIN, i64::MIN));
+        for (idx, pos) in positions.iter().enumerate() {
+            let cell = (
+                (pos.x / cell_size).floor() as i64,
+                (pos.y / cell_size).floor() as i64,
+            );
+            bounds.0 = (bounds.0 .0.min(cell.0), bounds.0 .1.min(cell.1));
+            bounds.1 = (bounds.1 .0.max(cell.0), bounds.1 .1.max(cell.1));
+            cells.entry(cell).or_default().push(idx);
+        }
+        Grid {
+            cell_size,
+            cells,
+            bounds,
+            positions,
+        }
+    }
+
+    pub fn cell_of(&self, idx: usize) -> (i64, i64) {
+        let pos = self.positions[idx];
+        (
+            (pos.x / self.cell_size).floor() as i64,
+            (pos.y / self.cell_size).floor() as i64,
+        )
+    }
+
+    /// All points in cells `ring` steps away from the cell at `center`.
+    pub fn ring(&self, center: (i64, i64), ring: i64) -> impl Iterator<Item = usize> + '_ {
+        (-ring..=ring)
+            .flat_map(move |dx| (-ring..=ring).map(move |dy| (dx, dy)))
+            .filter(move |(dx, dy)| dx.abs() == ring || dy.abs() == ring)
+            .filter_map(move |(dx, dy)| self.cells.get(&(center.0 + dx, center.1 + dy)))
+            .flatten()
+            .copied()
+    }
+
+    /// Squared distance to the nearest other point, if there is one.
+    pub fn nearest_squared(&self, idx: usize) -> Option<f64> {
+        let center = self.cell_of(idx);
+        let this = self.positions[idx];
+        let (min, max) = self.bounds;
+        let max_ring = (center.0 - min.0)
+            .max(max.0 - center.0)
+            .max(center.1 - min.1)
+            .max(max.1 - center.1);
+        let mut best: Option<f64> = None;
+        for ring in 0..=max_ring {
+            // Everything further out is at least this far away
+            let lower_bound = ((ring - 1).max(0) as f64 * self.cell_size).powf(2.0);
+            if matches!(best, Some(best) if best <= lower_bound) {
+                break;
+            }
+            for other in self.ring(center, ring).filter(|other| *other != idx) {
+                let dist = na::distance_squared(&this, &self.positions[other]);
+                if best.is_none_or(|best| dist < best) {
+                    best = Some(dist);
+                }
+            }
+        }
+        best
+    }
+}
@@ -0,0 +1,192 @@
+//! Run the simulation without a browser.
+//!
+//! Used by the native tools in `src/bin/`.
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use std::f64;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, path::Path};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::raster::Raster;
+use crate::{
+    boids::Boids, camera::Camera, coloring::Coloring, profile::Profiler, renderer, svg::Svg,
+    trails, utils as util, DEFAULT_TRAIL_OPACITY, SIMULATION_STEP,
+};
+
+pub use crate::{
+    coloring::ColorMode,
+    detail::Detail,
+    metrics::Metrics,
+    params::{Param, ALL as PARAMS},
+    presets::Preset,
+    recording::Recording,
+    shape::{Appearance, Shape},
+    theme::{ThemeKind, ThemeSettings},
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use tiny_skia::Pixmap;
+
+/// Seconds simulated by a single step in the browser. The behaviour depends
+/// on the step length, so use this to reproduce what the browser shows.
+pub const STEP_SECS: f64 = SIMULATION_STEP / 1000.0;
+
+/// A simulation that is stepped manually with a fixed time step.
+pub struct Runner {
+    pub boids: Boids,
+    pub appearance: Appearance,
+    /// Image drawn in place of every boid, if any
+    pub sprite: Option<Sprite>,
+    pub detail: Detail,
+    /// Theme to draw with, the system theme is light
+    pub theme: ThemeSettings,
+    profiler: Profiler,
+}
+
+impl Runner {
+    /// Create a simulation of the given size. `setup` is called to configure
+    /// the parameters before the boids are placed using the given `seed`.
+    ///
+    /// The mouse follower is removed, since there is no mouse.
+    pub fn new(width: f64, height: f64, seed: u64, setup: impl FnOnce(&mut Boids)) -> Self {
+        let mut boids = Boids::new(width, height);
+        setup(&mut boids);
+        boids.predators.remove(0);
+        boids.scatter_with_rng(&mut StdRng::seed_from_u64(seed));
+        // The metrics include the number of flocks
+        boids.set_track_flocks(true);
+        Runner {
+            boids,
+            appearance: Appearance::default(),
+            sprite: None,
+            detail: Detail::Full,
+            theme: ThemeSettings::default(),
+            profiler: Profiler::default(),
+        }
+    }
+
+    /// Advance the simulation by `secs` seconds.
+    pub fn step(&mut self, secs: f64) {
+        util::update(&mut self.boids, secs * 1000.0, &mut self.profiler);
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        Metrics::compute(&self.boids)
+    }
+
+    /// Draw the whole world into an image of the given size.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render(&self, width: u32, height: u32, color_mode: ColorMode) -> Option<Pixmap> {
+        let raster = Raster::new(width, height)?;
+        let mut camera = Camera::new((width as f64, height as f64));
+        camera.fit(self.boids.size);
+        // There is no operating system preference, the system theme is light
+        let theme = self.theme.resolve(false);
+        let coloring = Coloring::new(color_mode, &self.boids, &theme);
+        renderer::draw_background(&raster, &self.boids, &camera, &theme, false, 1.0);
+        trails::draw_trails(
+            &raster,
+            &self.boids,
+            &camera,
+            DEFAULT_TRAIL_OPACITY,
+            &coloring,
+        );
+        renderer::draw_boids(
+            &raster,
+            &self.boids,
+            &camera,
+            &coloring,
+            &self.appearance,
+            self.detail(&camera),
+            self.sprite.as_ref().map(|sprite| &sprite.pixmap),
+        );
+        Some(raster.into_pixmap())
+    }
+
+    /// Draw the whole world, including the predators, as SVG document of the given size.
+    pub fn to_svg(&self, width: f64, height: f64, color_mode: ColorMode) -> String {
+        let svg = Svg::new(width, height);
+        let mut camera = Camera::new((width, height));
+        camera.fit(self.boids.size);
+        // There is no operating system preference, the system theme is light
+        let theme = self.theme.resolve(false);
+        let coloring = Coloring::new(color_mode, &self.boids, &theme);
+        renderer::draw_background(&svg, &self.boids, &camera, &theme, false, 1.0);
+        trails::draw_trails(&svg, &self.boids, &camera, DEFAULT_TRAIL_OPACITY, &coloring);
+        renderer::draw_boids(
+            &svg,
+            &self.boids,
+            &camera,
+            &coloring,
+            &self.appearance,
+            self.detail(&camera),
+            self.sprite.as_ref().map(|sprite| &sprite.href),
+        );
+        renderer::draw_predators(&svg, &self.boids, &camera, &theme);
+        svg.finish()
+    }
+
+    fn detail(&self, camera: &Camera) -> Detail {
+        let screen_scale = camera.zoom * self.appearance.scale;
+        self.detail
+            .resolve(self.boids.boids.len(), screen_scale, false)
+    }
+}
+
+/// An image to draw boids as, in both image formats.
+pub struct Sprite {
+    /// Data url embedded into SVG documents
+    pub href: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub pixmap: Pixmap,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Sprite {
+    /// Load a PNG image from a file.
+    pub fn load_png(path: &Path) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let pixmap =
+            Pixmap::decode_png(&data).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Ok(Sprite {
+            href: format!("data:image/png;base64,{}", base64::encode(&data)),
+            pixmap,
+        })
+    }
+}
+
+/// Find a parameter by its url key, old keys are accepted, too.
+pub fn param_by_key(key: &str) -> Option<&'static Param> {
+    PARAMS
+        .iter()
+        .find(|param| param.key == key || param.legacy_key == key)
+        .copied()
+}
+
+/// Mean and standard deviation of a series of samples.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Summary {
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+impl Summary {
+    pub fn of(samples: impl IntoIterator<Item = f64>) -> Self {
+        let samples: Vec<_> = samples.into_iter().collect();
+        if samples.is_empty() {
+            return Summary::default();
+        }
+        let count = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / count;
+        let variance = samples
+            .iter()
+            .map(|sample| (sample - mean).powf(2.0))
+            .sum::<f64>()
+            / count;
+        Summary {
+            mean,
+            std_dev: variance.sqrt(),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{
+    model::Msg,
+    params::{self, Param},
+    TIME_SCALE_STEP,
+};
+
+/// What happens when a bound key is pressed.
+pub enum Action {
+    Send(fn() -> Msg),
+    /// Change the time scale by the given amount
+    TimeScale(f64),
+    /// Change a parameter by the given amount, using the message
+    /// the corresponding slider sends
+    Nudge(&'static Param, f64, fn(f64) -> Msg),
+}
+
+pub struct Binding {
+    /// Value of `KeyboardEvent.key` that triggers the action
+    pub key: &'static str,
+    /// How the key is displayed in the help
+    pub label: &'static str,
+    pub description: &'static str,
+    pub action: Action,
+}
+
+pub static BINDINGS: [Binding; 28] = [
+    Binding {
+        key: "h",
+        label: "h",
+        description: "Show/hide this help",
+        action: Action::Send(|| Msg::ToggleHelp),
+    },
+    Binding {
+        key: "p",
+        label: "p",
+        description: "Show/hide the settings panel",
+        action: Action::Send(|| Msg::TogglePanel),
+    },
+    Binding {
+        key: "d",
+        label: "d",
+        description: "Toggle debug mode",
+        action: Action::Send(|| Msg::ToggleDebugMode),
+    },
+    Binding {
+        key: "q",
+        label: "q",
+        description: "Toggle special mode",
+        action: Action::Send(|| Msg::ToggleSpecialMode),
+    },
+    Binding {
+        key: "k",
+        label: "k",
+        description: "Colour boids by the next quantity",
+        action: Action::Send(|| Msg::NextColorMode),
+    },
+    Binding {
+        key: "m",
+        label: "m",
+        description: "Show/hide the density heatmap",
+        action: Action::Send(|| Msg::ToggleHeatmap),
+    },
+    Binding {
+        key: "w",
+        label: "w",
+        description: "Show/hide the velocity field",
+        action: Action::Send(|| Msg::ToggleVelocityField),
+    },
+    Binding {
+        key: "Escape",
+        label: "Esc",
+        description: "Stop inspecting the selected boid",
+        action: Action::Send(|| Msg::StopInspecting),
+    },
+    Binding {
+        key: "r",
+        label: "r",
+        description: "Scatter the boids",
+        action: Action::Send(|| Msg::ScatterBoids),
+    },
+    Binding {
+        key: "0",
+        label: "0",
+        description: "Show the whole world",
+        action: Action::Send(|| Msg::ResetCamera),
+    },
+    Binding {
+        key: " ",
+        label: "Space",
+        description: "Pause/resume",
+        action: Action::Send(|| Msg::TogglePause),
+    },
+    Binding {
+        key: ".",
+        label: ".",
+        description: "Single step",
+        action: Action::Send(|| Msg::StepOnce),
+    },
+    Binding {
+        key: "+",
+        label: "+",
+        description: "Faster",
+        action: Action::TimeScale(TIME_SCALE_STEP),
+    },
+    Binding {
+        key: "-",
+        label: "-",
+        description: "Slower",
+        action: Action::TimeScale(-TIME_SCALE_STEP),
+    },
+    Binding {
+        key: "n",
+        label: "n",
+        description: "Fewer boids",
+        action: Action::Nudge(&params::NR_OF_BOIDS, -50.0, |val| {
+            Msg::ChangeNrOfBoids(val as usize)
+        }),
+    },
+    Binding {
+        key: "N",
+        label: "N",
+        description: "More boids",
+        action: Action::Nudge(&params::NR_OF_BOIDS, 50.0, |val| {
+            Msg::ChangeNrOfBoids(val as usize)
+        }),
+    },
+    Binding {
+        key: "a",
+        label: "a",
+        description: "Decrease align factor",
+        action: Action::Nudge(&params::ALIGN_FACTOR, -0.1, Msg::ChangeAlignFactor),
+    },
+    Binding {
+        key: "A",
+        label: "A",
+        description: "Increase align factor",
+        action: Action::Nudge(&params::ALIGN_FACTOR, 0.1, Msg::ChangeAlignFactor),
+    },
+    Binding {
+        key: "c",
+        label: "c",
+        description: "Decrease cohesion factor",
+        action: Action::Nudge(&params::COHESION_FACTOR, -0.01, Msg::ChangeCohesionFactor),
+    },
+    Binding {
+        key: "C",
+        label: "C",
+        description: "Increase cohesion factor",
+        action: Action::Nudge(&params::COHESION_FACTOR, 0.01, Msg::ChangeCohesionFactor),
+    },
+    Binding {
+        key: "s",
+        label: "s",
+        description: "Decrease seperation factor",
+        action: Action::Nudge(
+            &params::SEPERATION_FACTOR,
+            -0.1,
+            Msg::ChangeSeperationFactor,
+        ),
+    },
+    Binding {
+        key: "S",
+        label: "S",
+        description: "Increase seperation factor",
+        action: Action::Nudge(&params::SEPERATION_FACTOR, 0.1, Msg::ChangeSeperationFactor),
+    },
+    Binding {
+        key: "f",
+        label: "f",
+        description: "Decrease angst factor",
+        action: Action::Nudge(&params::ANGST_FACTOR, -500.0, Msg::ChangeAngstFactor),
+    },
+    Binding {
+        key: "F",
+        label: "F",
+        description: "Increase angst factor",
+        action: Action::Nudge(&params::ANGST_FACTOR, 500.0, Msg::ChangeAngstFactor),
+    },
+    Binding {
+        key: "v",
+        label: "v",
+        description: "Decrease speed limit",
+        action: Action::Nudge(&params::MAX_SPEED, -10.0, Msg::ChangeMaxSpeed),
+    },
+    Binding {
+        key: "V",
+        label: "V",
+        description: "Increase speed limit",
+        action: Action::Nudge(&params::MAX_SPEED, 10.0, Msg::ChangeMaxSpeed),
+    },
+    Binding {
+        key: "x",
+        label: "x",
+        description: "Decrease acceleration limit",
+        action: Action::Nudge(&params::MAX_STEER, -1.0, Msg::ChangeMaxSteer),
+    },
+    Binding {
+        key: "X",
+        label: "X",
+        description: "Increase acceleration limit",
+        action: Action::Nudge(&params::MAX_STEER, 1.0, Msg::ChangeMaxSteer),
+    },
+];
+
+/// Find the binding for the given `KeyboardEvent.key`.
+pub fn binding_for(key: &str) -> Option<&'static Binding> {
+    BINDINGS.iter().find(|binding| binding.key == key)
+}
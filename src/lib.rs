@@ -1,23 +1,80 @@
-#![recursion_limit = "1024"]
+#![recursion_limit = "4096"]
 
 use wasm_bindgen::prelude::*;
 use yew::prelude::*;
 
+#[macro_use]
+mod macros;
+
 mod boid;
 mod boids;
+mod camera;
+mod clock;
+mod clusters;
+mod coloring;
 mod debug;
+mod detail;
+mod draw;
+mod field;
+mod grid;
+pub mod headless;
+mod keys;
+mod metrics;
 mod model;
+mod params;
+mod presets;
+mod profile;
+mod quality;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod raster;
+mod recording;
+mod renderer;
+mod resize;
+mod shape;
+mod svg;
+mod theme;
+mod touch;
+mod trails;
 mod utils;
+mod webgl;
 
 use model::Model;
 
 const CANVAS_ID: &str = "canvas";
+/// Canvas below the main canvas, used by the WebGL renderer
+const GL_CANVAS_ID: &str = "gl-canvas";
 
 const QR_CODE_ID: &str = "qrcode";
 const QR_CODE_LOCATION: &str = "qrcode.png";
-const QR_CODE_SIZE: f64 = 30.0;
+/// Side length of sprites at scale one
+const SPRITE_SIZE: f64 = 30.0;
+/// Hidden image holding the sprite chosen in the settings panel
+const SPRITE_ID: &str = "sprite";
+const MIN_BOID_SCALE: f64 = 0.5;
+const MAX_BOID_SCALE: f64 = 5.0;
+/// Length of a boid at scale one, from the tail to the tip
+const BOID_LENGTH: f64 = 11.0;
+/// Above this many boids, automatic level of detail draws dots
+const LOD_DOT_BOIDS: usize = 5000;
+/// Above this many boids, automatic level of detail draws single pixels
+const LOD_PIXEL_BOIDS: usize = 20000;
+/// Boids shorter than this many screen pixels are drawn as dots
+const LOD_DOT_SIZE: f64 = 6.0;
+/// Boids shorter than this many screen pixels are drawn as single pixels
+const LOD_PIXEL_SIZE: f64 = 2.0;
+
+/// Version of the url encoding written by `Boids::to_url_suffix`
+const URL_VERSION: u8 = 2;
 
 const PREDATOR_CLICK_RADIUS_SQUARED: f64 = 15.0;
+/// Shift clicks select the nearest boid within this many screen pixels
+const INSPECT_RADIUS: f64 = 20.0;
+/// Length of a steering vector of the inspected boid at maximum steer
+const STEER_VECTOR_LENGTH: f64 = 50.0;
+/// Touches shorter than this many milliseconds may count as tap
+const TAP_MAX_DURATION: f64 = 300.0;
+/// Touches that moved further than this do not count as tap
+const TAP_MAX_DISTANCE_SQUARED: f64 = 100.0;
 
 const DEFAULT_NR_OF_BOIDS: usize = 100;
 const DEFAULT_MAX_SPEED: f64 = 300.0;
@@ -31,21 +88,70 @@ const DEFAULT_COHESION_FACTOR: f64 = 1.0 / 100.0;
 const DEFAULT_SEPERATION_FACTOR: f64 = 1.0;
 const DEFAULT_ANGST_FACTOR: f64 = 2000.0;
 
-const BG_COLOR: &str = "#d8dee9";
-const BOID_COLOR: &str = "#bf616a";
-const ALIGN_RADIUS_COLOR: &str = "red";
-const COHESION_RADIUS_COLOR: &str = "green";
-const SEPERATION_RADIUS_COLOR: &str = "blue";
-const VELOCITY_COLOR: &str = "white";
-const PREDATOR_RADIUS_COLOR: &str = "red";
-const BORDER_COLOR: &str = "green";
-const BUCKET_GRID_COLOR: &str = "#4c566a33";
-const STATISTICS_COLOR: &str = "#666666";
+const FLOCK_COLORS: [&str; 8] = [
+    "#d08770", "#ebcb8b", "#a3be8c", "#b48ead", "#5e81ac", "#88c0d0", "#8fbcbb", "#4c566a",
+];
+/// Palette for quantities like speed, from low to high
+const QUANTITY_COLORS: [&str; 8] = [
+    "#440154", "#46327e", "#365c8d", "#277f8e", "#1fa187", "#4ac16d", "#a0da39", "#fde725",
+];
+/// Opacity of the density heatmap overlay
+const HEATMAP_OPACITY: f64 = 0.6;
+/// Cyclic palette for the heading, starting north and going clockwise
+const HEADING_COLORS: [&str; 8] = [
+    "#bf616a", "#d08770", "#ebcb8b", "#a3be8c", "#8fbcbb", "#88c0d0", "#5e81ac", "#b48ead",
+];
+const LEGEND_SWATCH_SIZE: f64 = 20.0;
+/// Radius of the circle the local density is measured in
+const DENSITY_RADIUS: f64 = 50.0;
 
 const RETURN_STEER_VAL: f64 = 10.0;
 const WALL_SIZE: f64 = 100.0;
 const BUCKET_SIZE: usize = 50;
 
+/// Milliseconds simulated by a single step
+const SIMULATION_STEP: f64 = 10.0;
+/// Simulation steps per animation frame and unit of time scale are
+/// limited to this, so that slow frames do not cause even slower frames
+const MAX_STEPS_PER_FRAME: usize = 10;
+const MIN_ZOOM: f64 = 0.1;
+const MAX_ZOOM: f64 = 10.0;
+/// Zoom factor per pixel scrolled is `exp(WHEEL_ZOOM_SPEED)`
+const WHEEL_ZOOM_SPEED: f64 = 0.001;
+const MIN_TIME_SCALE: f64 = 0.1;
+const MAX_TIME_SCALE: f64 = 4.0;
+const TIME_SCALE_STEP: f64 = 0.1;
+
+/// Number of steps the metrics are kept for the debug charts
+const METRICS_HISTORY_LENGTH: usize = 200;
+const NN_HISTOGRAM_BINS: usize = 10;
+const NN_HISTOGRAM_BIN_SIZE: f64 = 5.0;
+const METRICS_CHART_WIDTH: f64 = 200.0;
+const METRICS_CHART_HEIGHT: f64 = 20.0;
+const FLOCK_HISTOGRAM_BINS: usize = 8;
+/// Number of frames the frame time graph shows
+const FRAME_TIME_HISTORY_LENGTH: usize = 200;
+/// Weight of the newest frame in the rolling profile averages
+const PROFILE_SMOOTHING: f64 = 0.05;
+/// Only every this many boids the simulation phases are timed
+const PROFILE_SAMPLE_EVERY: usize = 16;
+
+/// Milliseconds between two adjustments of the adaptive quality
+const QUALITY_ADJUST_INTERVAL: f64 = 1000.0;
+/// Relative deviation from the target frame rate that is tolerated
+const QUALITY_TOLERANCE: f64 = 0.1;
+/// The adaptive quality never suspends boids below this number
+const QUALITY_MIN_BOIDS: usize = 50;
+const DEFAULT_TRAIL_LENGTH: usize = 30;
+const DEFAULT_TRAIL_OPACITY: f64 = 0.5;
+const DEFAULT_RECORD_FRAMES: usize = 100;
+const DEFAULT_RECORD_FPS: f64 = 20.0;
+/// Size of recordings relative to the window
+const DEFAULT_RECORD_SCALE: f64 = 0.5;
+/// Captured frames kept before they are encoded right away, to bound the
+/// memory used if encoding between animation frames falls behind
+const MAX_PENDING_RECORD_FRAMES: usize = 10;
+
 /// Start here!
 #[wasm_bindgen(start)]
 pub fn run_app() {
@@ -0,0 +1,75 @@
+/// Define a fieldless enum whose variants have a name and a label.
+///
+/// Every variant is written as `Variant => ("name", "Label")`. Besides the
+/// enum this generates `ALL`, the variants in declaration order, `name`,
+/// `label` and a `FromStr` that looks variants up by their name.
+macro_rules! named_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $enum:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident => ($name:literal, $label:literal),
+            )+
+        }
+    ) => {
+        $(#[$meta])*
+        pub enum $enum {
+            $(
+                $(#[$variant_meta])*
+                $variant,
+            )+
+        }
+
+        impl $enum {
+            pub const ALL: [$enum; [$($name),+].len()] = [$($enum::$variant),+];
+
+            /// Name used as option value in the settings panel and in the url.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $($enum::$variant => $name,)+
+                }
+            }
+
+            pub fn label(&self) -> &'static str {
+                match self {
+                    $($enum::$variant => $label,)+
+                }
+            }
+        }
+
+        impl ::std::str::FromStr for $enum {
+            type Err = ();
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $enum::ALL
+                    .iter()
+                    .find(|variant| variant.name() == s)
+                    .copied()
+                    .ok_or(())
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    named_enum! {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub enum Example {
+            First => ("first", "First One"),
+            /// Documented
+            Second => ("second", "Second One"),
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        assert_eq!(Example::ALL, [Example::First, Example::Second]);
+        for variant in Example::ALL.iter() {
+            assert_eq!(variant.name().parse(), Ok(*variant));
+        }
+        assert_eq!(Example::Second.label(), "Second One");
+        assert_eq!("Second One".parse::<Example>(), Err(()));
+    }
+}
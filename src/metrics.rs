@@ -0,0 +1,230 @@
+use na::{Point2, Vector2};
+use nalgebra as na;
+
+use std::{collections::VecDeque, f64};
+
+use crate::{
+    boids::Boids, grid::Grid, METRICS_HISTORY_LENGTH, NN_HISTOGRAM_BINS, NN_HISTOGRAM_BIN_SIZE,
+};
+
+/// Order parameters and other statistics describing the flock at one step.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    /// Length of the mean normalised velocity, 1 if all boids head the same way
+    pub polarization: f64,
+    /// Normalised angular momentum around the center of mass,
+    /// 1 if all boids circle the center in the same direction
+    pub milling: f64,
+    /// Mean distance to the nearest neighbour
+    pub mean_nn_distance: f64,
+    /// Distribution of nearest neighbour distances. Bin `i` counts distances in
+    /// `[i * NN_HISTOGRAM_BIN_SIZE, (i + 1) * NN_HISTOGRAM_BIN_SIZE)`,
+    /// the last bin includes all larger distances
+    pub nn_histogram: [usize; NN_HISTOGRAM_BINS],
+    /// Number of flocks with more than one member,
+    /// see [`Flocks::group_count`](crate::clusters::Flocks::group_count)
+    pub clusters: usize,
+    pub mean_speed: f64,
+    /// Boids per 100x100 pixels
+    pub density: f64,
+}
+
+impl Metrics {
+    pub fn compute(boids: &Boids) -> Self {
+        let nr = boids.boids.len();
+        if nr == 0 {
+            return Metrics::default();
+        }
+        let count = nr as f64;
+        let center = boids
+            .boids
+            .iter()
+            .fold(Vector2::zeros(), |sum, boid| sum + boid.pos)
+            / count;
+        let mut heading_sum: Vector2<f64> = na::zero();
+        let mut momentum_sum = 0.0;
+        let mut speed_sum = 0.0;
+        for boid in &boids.boids {
+            let speed = boid.vel.norm();
+            speed_sum += speed;
+            if speed > f64::EPSILON {
+                let heading = boid.vel / speed;
+                heading_sum += heading;
+                let arm = boid.pos - center;
+                let arm_len = arm.norm();
+                if arm_len > f64::EPSILON {
+                    momentum_sum += (arm.x * heading.y - arm.y * heading.x) / arm_len;
+                }
+            }
+        }
+        let positions: Vec<_> = boids
+            .boids
+            .iter()
+            .map(|boid| Point2::origin() + boid.pos)
+            .collect();
+        let (mean_nn_distance, nn_histogram) = nearest_neighbours(&positions);
+        let area = boids.size.0 * boids.size.1;
+        let density = if area > f64::EPSILON {
+            count / area * 100.0 * 100.0
+        } else {
+            0.0
+        };
+        Metrics {
+            polarization: heading_sum.norm() / count,
+            milling: momentum_sum.abs() / count,
+            mean_nn_distance,
+            nn_histogram,
+            clusters: boids.flocks.group_count(),
+            mean_speed: speed_sum / count,
+            density,
+        }
+    }
+}
+
+/// The most recent [`Metrics`], oldest first.
+pub struct MetricsHistory {
+    samples: VecDeque<Metrics>,
+}
+
+impl MetricsHistory {
+    pub fn new() -> Self {
+        MetricsHistory {
+            samples: VecDeque::with_capacity(METRICS_HISTORY_LENGTH),
+        }
+    }
+
+    pub fn push(&mut self, metrics: Metrics) {
+        if self.samples.len() == METRICS_HISTORY_LENGTH {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(metrics);
+    }
+
+    pub fn latest(&self) -> Option<&Metrics> {
+        self.samples.back()
+    }
+
+    /// Get the history of a single quantity, oldest first.
+    pub fn series<'a>(
+        &'a self,
+        quantity: impl Fn(&Metrics) -> f64 + 'a,
+    ) -> impl Iterator<Item = f64> + 'a {
+        self.samples.iter().map(quantity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+}
+
+fn nearest_neighbours(positions: &[Point2<f64>]) -> (f64, [usize; NN_HISTOGRAM_BINS]) {
+    let grid = Grid::new(positions, NN_HISTOGRAM_BIN_SIZE * NN_HISTOGRAM_BINS as f64);
+    let mut histogram = [0; NN_HISTOGRAM_BINS];
+    let (sum, count) = (0..positions.len())
+        .filter_map(|idx| grid.nearest_squared(idx))
+        .map(f64::sqrt)
+        .inspect(|dist| {
+            let bin = (dist / NN_HISTOGRAM_BIN_SIZE) as usize;
+            histogram[bin.min(NN_HISTOGRAM_BINS - 1)] += 1;
+        })
+        .fold((0.0, 0), |(sum, count), dist| (sum + dist, count + 1));
+    let mean = if count != 0 { sum / count as f64 } else { 0.0 };
+    (mean, histogram)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boid::Boid;
+
+    /// Position and velocity of a boid
+    type State = ((f64, f64), (f64, f64));
+
+    /// Boids in a 1000 by 1000 world with the given positions and velocities.
+    fn boids_with(states: &[State]) -> Boids {
+        let mut boids = Boids::new(1000.0, 1000.0);
+        boids.boids = states
+            .iter()
+            .map(|((x, y), (vx, vy))| {
+                let mut boid = Boid::new(1000.0, 1000.0);
+                boid.pos = Vector2::new(*x, *y);
+                boid.vel = Vector2::new(*vx, *vy);
+                boid
+            })
+            .collect();
+        boids
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn aligned_boids_are_polarized() {
+        let boids = boids_with(&[
+            ((100.0, 500.0), (10.0, 0.0)),
+            ((200.0, 500.0), (50.0, 0.0)),
+            ((300.0, 500.0), (30.0, 0.0)),
+        ]);
+        let metrics = Metrics::compute(&boids);
+        assert_close(metrics.polarization, 1.0);
+        assert_close(metrics.milling, 0.0);
+        assert_close(metrics.mean_speed, 30.0);
+    }
+
+    #[test]
+    fn rotating_ring_is_milling() {
+        let states: Vec<_> = (0..12)
+            .map(|nr| {
+                let phi = nr as f64 * 2.0 * f64::consts::PI / 12.0;
+                let (sin, cos) = phi.sin_cos();
+                // Tangential velocity, counterclockwise around the center
+                ((500.0 + 200.0 * cos, 500.0 + 200.0 * sin), (-sin, cos))
+            })
+            .collect();
+        let metrics = Metrics::compute(&boids_with(&states));
+        assert_close(metrics.milling, 1.0);
+        assert_close(metrics.polarization, 0.0);
+    }
+
+    #[test]
+    fn opposed_pairs_are_disordered() {
+        let boids = boids_with(&[
+            ((400.0, 500.0), (1.0, 0.0)),
+            ((600.0, 500.0), (-1.0, 0.0)),
+            ((500.0, 400.0), (0.0, 1.0)),
+            ((500.0, 600.0), (0.0, -1.0)),
+        ]);
+        let metrics = Metrics::compute(&boids);
+        assert_close(metrics.polarization, 0.0);
+        assert_close(metrics.milling, 0.0);
+    }
+
+    #[test]
+    fn nearest_neighbours_of_a_row() {
+        let boids = boids_with(&[
+            ((100.0, 500.0), (1.0, 0.0)),
+            ((112.0, 500.0), (1.0, 0.0)),
+            ((124.0, 500.0), (1.0, 0.0)),
+            ((900.0, 500.0), (1.0, 0.0)),
+        ]);
+        let metrics = Metrics::compute(&boids);
+        // The lone boid is 776 away from the row and lands in the last bin
+        assert_close(metrics.mean_nn_distance, (3.0 * 12.0 + 776.0) / 4.0);
+        let mut histogram = [0; NN_HISTOGRAM_BINS];
+        histogram[(12.0 / NN_HISTOGRAM_BIN_SIZE) as usize] = 3;
+        histogram[NN_HISTOGRAM_BINS - 1] = 1;
+        assert_eq!(metrics.nn_histogram, histogram);
+        // Four boids on 100 areas of 100x100
+        assert_close(metrics.density, 0.04);
+    }
+
+    #[test]
+    fn no_boids_give_zeros() {
+        let boids = boids_with(&[]);
+        let metrics = Metrics::compute(&boids);
+        assert_eq!(metrics.polarization, 0.0);
+        assert_eq!(metrics.milling, 0.0);
+        assert_eq!(metrics.mean_nn_distance, 0.0);
+    }
+}
@@ -4,35 +4,164 @@ use nalgebra::{self as na, Vector2};
 use wasm_bindgen::{prelude::*, JsCast};
 use yew::{
     prelude::*,
-    services::interval::{IntervalService, IntervalTask},
+    services::{
+        keyboard::{KeyListenerHandle, KeyboardService},
+        render::{RenderService, RenderTask},
+        resize::{ResizeService, ResizeTask, WindowDimensions},
+        timeout::{TimeoutService, TimeoutTask},
+    },
     utils::document,
-    web_sys::{CanvasRenderingContext2d, MouseEvent},
+    web_sys::{
+        CanvasRenderingContext2d, File, HtmlElement, HtmlImageElement, KeyboardEvent, MouseEvent,
+        TouchEvent, Url, WheelEvent,
+    },
 };
 
-use std::{f64, time::Duration};
+use std::{cell::RefCell, f64, time::Duration};
 
 use crate::{
-    boids::Boids, debug, utils as util, BG_COLOR, BOID_COLOR, CANVAS_ID,
-    PREDATOR_CLICK_RADIUS_SQUARED, QR_CODE_ID, QR_CODE_LOCATION,
+    boids::Boids,
+    camera::Camera,
+    clock::StepClock,
+    coloring::{self, ColorMode, Coloring},
+    debug,
+    detail::Detail,
+    draw::Draw,
+    field::{self, CellField},
+    keys::{self, Action},
+    metrics::{Metrics, MetricsHistory},
+    params,
+    presets::Preset,
+    profile::{self, Phase, Profiler},
+    quality::QualityController,
+    recording::Recording,
+    renderer::{self, CanvasRenderer, Renderer, RendererKind},
+    resize::ResizePolicy,
+    shape::{Appearance, Shape},
+    svg::Svg,
+    theme::{Theme, ThemeKind, ThemeSettings},
+    touch::TouchTracker,
+    trails::{self, TrailMode},
+    utils as util, CANVAS_ID, DEFAULT_RECORD_FPS, DEFAULT_RECORD_FRAMES, DEFAULT_RECORD_SCALE,
+    DEFAULT_TRAIL_LENGTH, DEFAULT_TRAIL_OPACITY, GL_CANVAS_ID, INSPECT_RADIUS, MAX_BOID_SCALE,
+    MAX_PENDING_RECORD_FRAMES, MAX_TIME_SCALE, MIN_BOID_SCALE, MIN_TIME_SCALE,
+    PREDATOR_CLICK_RADIUS_SQUARED, QR_CODE_ID, QR_CODE_LOCATION, SIMULATION_STEP, SPRITE_ID,
+    TAP_MAX_DISTANCE_SQUARED, TIME_SCALE_STEP, WHEEL_ZOOM_SPEED,
 };
 
 pub struct Model {
     pub boids: Boids,
+    pub camera: Camera,
+    /// Size of the world relative to the window
+    pub world_scale: f64,
+    /// How the world adapts when the window is resized
+    pub resize_policy: ResizePolicy,
+    /// Kind of the active renderer, might differ from the selected one
+    /// if it is not supported
+    pub renderer_kind: RendererKind,
+    renderer: RefCell<Box<dyn Renderer>>,
+    /// Last position and total distance of the mouse while dragging the view
+    pub drag: Option<(Vector2<f64>, f64)>,
+    /// Set when a drag ended, the following click must not place a predator
+    pub suppress_click: bool,
+    /// Screen positions of the two fingers during a pinch gesture
+    pub pinch: Option<(Vector2<f64>, Vector2<f64>)>,
+    /// Timestamp of the last animation frame
     pub last_update: f64,
+    /// Milliseconds between the last two animation frames
     pub last_time_passed: f64,
+    pub paused: bool,
+    /// Turns the time between frames into simulation steps
+    pub clock: StepClock,
+    /// Flock metrics, only collected in debug mode
+    pub metrics: MetricsHistory,
+    /// Time spent in the parts of a frame, only measured in debug mode
+    pub profiler: Profiler,
+    pub quality: QualityController,
+    pub touches: TouchTracker,
     pub link: ComponentLink<Self>,
     pub settings_panel_shown: bool,
+    pub help_shown: bool,
     pub special_mode: bool,
-    _task: Box<IntervalTask>,
+    /// The selected theme, stored in the url
+    pub theme_settings: ThemeSettings,
+    /// Colours of the selected theme, see [`Model::theme_settings`]
+    pub theme: Theme,
+    /// Whether the operating system uses a dark colour scheme
+    pub prefers_dark: bool,
+    pub color_mode: ColorMode,
+    /// Colours of the boids in the current frame
+    pub coloring: Coloring,
+    pub appearance: Appearance,
+    /// Source of the image drawn for [`Shape::Sprite`], a url or an object url of a file
+    pub sprite_url: String,
+    /// Why the image at [`Model::sprite_url`] could not be loaded, if it failed
+    pub sprite_error: Option<String>,
+    /// The selected level of detail, see [`Model::detail`]
+    pub detail: Detail,
+    /// Whether to draw the number of boids per bucket as heatmap
+    pub show_heatmap: bool,
+    /// Whether to draw the mean velocity per bucket as arrows
+    pub show_velocity_field: bool,
+    /// Index of the boid selected for inspection, see [`Model::inspected`]
+    inspected: Option<usize>,
+    /// Whether to encode the parameters in the url as compact blob
+    pub compact_url: bool,
+    /// The preset last applied, the url only stores changes to it
+    pub preset: Option<Preset>,
+    pub trail_mode: TrailMode,
+    /// Number of positions in a trail, if trails are drawn as lines
+    pub trail_length: usize,
+    /// Opacity of the newest part of a trail
+    pub trail_opacity: f64,
+    /// The running recording, if any
+    pub recording: Option<Recording>,
+    /// Number of frames a recording captures
+    pub record_frames: usize,
+    pub record_fps: f64,
+    /// Size of recordings relative to the window
+    pub record_scale: f64,
+    /// Encodes the next captured frame between animation frames
+    _encode_task: Option<TimeoutTask>,
+    _render_task: RenderTask,
+    _visibility_listener: Closure<dyn FnMut()>,
+    _color_scheme_listener: Closure<dyn FnMut()>,
+    _key_listener: KeyListenerHandle,
+    _resize_task: ResizeTask,
 }
 
 #[derive(Debug, Clone)]
 pub enum Msg {
-    Tick,
+    /// An animation frame with its timestamp
+    Frame(f64),
+    VisibilityChanged,
+    /// The colour scheme of the operating system changed
+    ColorSchemeChanged,
+    KeyDown(KeyboardEvent),
+    TogglePause,
+    StepOnce,
+    ChangeTimeScale(f64),
     TogglePanel,
+    ToggleHelp,
     ToggleDebugMode,
     ToggleSpecialMode,
+    NextColorMode,
+    ChangeColorMode(ColorMode),
+    ChangeShape(Shape),
+    ChangeBoidScale(f64),
+    ChangeSpriteUrl(String),
+    /// An image file was chosen as sprite
+    ChangeSpriteFile(File),
+    SpriteLoaded,
+    SpriteFailed,
+    ToggleSpriteRotation,
+    ChangeDetail(Detail),
+    ToggleHeatmap,
+    ToggleVelocityField,
+    StopInspecting,
+    ToggleCompactUrl,
     TogglePredator(MouseEvent),
+    Touch(TouchEvent),
     ChangeAlignRadius(f64),
     ChangeCohesionRadius(f64),
     ChangeSeperationRadius(f64),
@@ -44,7 +173,33 @@ pub enum Msg {
     ChangeCohesionFactor(f64),
     ChangeSeperationFactor(f64),
     ChangeAngstFactor(f64),
+    ChangeTargetFps(f64),
+    ApplyPreset(Preset),
     MouseMoved(MouseEvent),
+    MouseDown(MouseEvent),
+    MouseUp,
+    Wheel(WheelEvent),
+    ResetCamera,
+    /// The window was resized to the given size
+    Resized((f64, f64)),
+    ChangeResizePolicy(ResizePolicy),
+    ChangeRenderer(RendererKind),
+    ExportSvg,
+    ChangeTheme(ThemeKind),
+    ChangeCustomBackground(String),
+    ChangeCustomBoidColor(String),
+    ChangeCustomPredatorColor(String),
+    ChangeTrailMode(TrailMode),
+    ChangeTrailLength(usize),
+    ChangeTrailOpacity(f64),
+    /// Start a recording or stop and download the current one
+    ToggleRecording,
+    /// Encode a captured frame of the running recording
+    EncodeFrame,
+    ChangeRecordFrames(usize),
+    ChangeRecordFps(f64),
+    ChangeRecordScale(f64),
+    ChangeWorldScale(f64),
     ScatterBoids,
 }
 
@@ -53,62 +208,273 @@ impl Component for Model {
     type Properties = ();
 
     fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let callback = link.callback(|_| Msg::Tick);
-        let handle = IntervalService::spawn(Duration::from_millis(10), callback);
+        let render_task = RenderService::request_animation_frame(link.callback(Msg::Frame));
+        let visibility_listener =
+            util::on_visibility_change(link.callback(|_| Msg::VisibilityChanged));
+        let color_scheme_listener =
+            util::on_color_scheme_change(link.callback(|_| Msg::ColorSchemeChanged));
+        let key_listener =
+            KeyboardService::register_key_down(&document(), link.callback(Msg::KeyDown));
+        let resize_task =
+            ResizeService::new().register(link.callback(|dimensions: WindowDimensions| {
+                Msg::Resized((dimensions.width as f64, dimensions.height as f64))
+            }));
         let (width, height) = util::get_window_size();
         let settings_panel_shown = false;
+        let help_shown = false;
         let special_mode = false;
-        let mut boids = Boids::new(width, height);
-        util::update_boids_from_url(&mut boids);
-        util::update_url(&boids.to_url_suffix());
-        Self {
+        let world_scale = 1.0;
+        let mut boids = Boids::new(width * world_scale, height * world_scale);
+        let mut preset = None;
+        let mut theme_settings = ThemeSettings::default();
+        let compact_url = util::update_from_url(&mut boids, &mut preset, &mut theme_settings);
+        let prefers_dark = util::prefers_dark_color_scheme();
+        let theme = theme_settings.resolve(prefers_dark);
+        let coloring = Coloring::new(ColorMode::Uniform, &boids, &theme);
+        let model = Self {
             boids,
+            camera: Camera::new((width, height)),
+            world_scale,
+            resize_policy: ResizePolicy::Rescale,
+            renderer_kind: RendererKind::Canvas,
+            renderer: RefCell::new(Box::new(CanvasRenderer)),
+            drag: None,
+            suppress_click: false,
+            pinch: None,
             last_update: util::performance().now(),
             last_time_passed: 0.0,
+            paused: false,
+            clock: StepClock::new(),
+            metrics: MetricsHistory::new(),
+            profiler: Profiler::default(),
+            quality: QualityController::new(),
+            touches: TouchTracker::new(),
             link,
             settings_panel_shown,
+            help_shown,
             special_mode,
-            _task: Box::new(handle),
-        }
+            theme_settings,
+            theme,
+            prefers_dark,
+            color_mode: ColorMode::Uniform,
+            coloring,
+            appearance: Appearance::default(),
+            sprite_url: String::from(QR_CODE_LOCATION),
+            sprite_error: None,
+            detail: Detail::Auto,
+            show_heatmap: false,
+            show_velocity_field: false,
+            inspected: None,
+            compact_url,
+            preset,
+            trail_mode: TrailMode::Off,
+            trail_length: DEFAULT_TRAIL_LENGTH,
+            trail_opacity: DEFAULT_TRAIL_OPACITY,
+            recording: None,
+            record_frames: DEFAULT_RECORD_FRAMES,
+            record_fps: DEFAULT_RECORD_FPS,
+            record_scale: DEFAULT_RECORD_SCALE,
+            _encode_task: None,
+            _render_task: render_task,
+            _visibility_listener: visibility_listener,
+            _color_scheme_listener: color_scheme_listener,
+            _key_listener: key_listener,
+            _resize_task: resize_task,
+        };
+        model.update_url();
+        util::set_page_theme(model.theme.is_dark());
+        model
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match &msg {
-            Msg::Tick => {
-                let now = util::performance().now();
+            Msg::Frame(now) => {
+                self._render_task =
+                    RenderService::request_animation_frame(self.link.callback(Msg::Frame));
                 self.last_time_passed = now - self.last_update;
-                self.last_update = now;
+                self.last_update = *now;
+                self.profiler.finish_frame(self.last_time_passed);
+                self.profiler.enabled = self.boids.debug_mode;
+                let work_start = profile::now();
+                // Detecting flocks is expensive, only do it if something shows them
+                let track_flocks = self.color_mode == ColorMode::Flock
+                    || self.boids.debug_mode
+                    || self.inspected().is_some();
+                self.boids.set_track_flocks(track_flocks);
 
-                self.boids.size = util::get_window_size();
-                util::update(&mut self.boids, self.last_time_passed);
+                // Step the simulation with a fixed step size, independent of the frame rate
+                let steps = if self.paused {
+                    0
+                } else {
+                    self.clock.advance(self.last_time_passed)
+                };
+                for _ in 0..steps {
+                    self.step();
+                }
+                if self.boids.debug_mode && steps > 0 {
+                    self.metrics.push(Metrics::compute(&self.boids));
+                }
+                self.render();
+                let work_time = profile::now() - work_start;
+                self.quality
+                    .update(&mut self.boids, work_time, self.last_time_passed);
+                self.capture_frame(steps as f64 * SIMULATION_STEP);
+            }
+            Msg::VisibilityChanged => {
+                // Browsers stop sending animation frames for hidden pages,
+                // do not try to catch up on the time that passed meanwhile
+                self.last_update = util::performance().now();
+                self.clock.reset();
+            }
+            Msg::KeyDown(event) => self.handle_key(event),
+            Msg::TogglePause => {
+                self.paused = !self.paused;
+                self.clock.reset();
+            }
+            Msg::StepOnce => {
+                self.paused = true;
+                self.step();
+                // Paused frames do not step, keep the charts going meanwhile
+                if self.boids.debug_mode {
+                    self.metrics.push(Metrics::compute(&self.boids));
+                }
+            }
+            Msg::ChangeTimeScale(scale) => {
+                self.clock.time_scale = scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE)
             }
             Msg::MouseMoved(me) => {
-                self.boids.predators[0].x = me.client_x() as f64;
-                self.boids.predators[0].y = me.client_y() as f64;
+                let screen = Vector2::new(me.client_x() as f64, me.client_y() as f64);
+                // The button may have been released outside of the canvas
+                if me.buttons() & 1 == 0 {
+                    self.drag = None;
+                }
+                if let Some((last, distance)) = &mut self.drag {
+                    self.camera.pan(screen - *last);
+                    *distance += (screen - *last).norm();
+                    *last = screen;
+                }
+                self.boids.predators[0] = self.camera.to_world(screen);
+                // Hovering with shift inspects boids, but keeps the last one elsewhere
+                if me.shift_key() {
+                    if let Some(idx) = self.boid_at(screen) {
+                        self.inspected = Some(idx);
+                    }
+                }
+            }
+            Msg::MouseDown(me) => {
+                // Only the primary button pans
+                if me.button() == 0 {
+                    let screen = Vector2::new(me.client_x() as f64, me.client_y() as f64);
+                    self.drag = Some((screen, 0.0));
+                }
+            }
+            Msg::MouseUp => {
+                if let Some((_, distance)) = self.drag.take() {
+                    self.suppress_click = distance.powf(2.0) > TAP_MAX_DISTANCE_SQUARED;
+                }
+            }
+            Msg::Wheel(event) => {
+                event.prevent_default();
+                let screen = Vector2::new(event.client_x() as f64, event.client_y() as f64);
+                let factor = (-event.delta_y() * WHEEL_ZOOM_SPEED).exp();
+                self.camera.zoom_at(screen, factor);
+            }
+            Msg::ResetCamera => self.camera.fit(self.boids.size),
+            Msg::ChangeWorldScale(scale) => {
+                self.world_scale = *scale;
+                // Letterboxing would ignore the new scale, so always rescale
+                let view = self.camera.view;
+                ResizePolicy::Rescale.resize(&mut self.boids, &mut self.camera, view, *scale);
+            }
+            Msg::Resized(view) => {
+                self.resize_policy.resize(
+                    &mut self.boids,
+                    &mut self.camera,
+                    *view,
+                    self.world_scale,
+                );
+            }
+            Msg::ChangeResizePolicy(policy) => self.resize_policy = *policy,
+            Msg::ExportSvg => {
+                util::download("boids.svg", "image/svg+xml", self.to_svg().as_bytes())
+            }
+            Msg::ColorSchemeChanged => {
+                self.prefers_dark = util::prefers_dark_color_scheme();
+                self.apply_theme();
+            }
+            Msg::ChangeTheme(kind) => {
+                self.theme_settings.kind = *kind;
+                self.apply_theme();
+            }
+            Msg::ChangeCustomBackground(color) => {
+                self.theme_settings.custom.background = color.clone();
+                self.apply_theme();
+            }
+            Msg::ChangeCustomBoidColor(color) => {
+                self.theme_settings.custom.boid = color.clone();
+                self.apply_theme();
+            }
+            Msg::ChangeCustomPredatorColor(color) => {
+                self.theme_settings.custom.predator = color.clone();
+                self.apply_theme();
+            }
+            Msg::ChangeTrailMode(mode) => {
+                self.trail_mode = *mode;
+                self.apply_trail_length();
+            }
+            Msg::ChangeTrailLength(length) => {
+                self.trail_length = *length;
+                self.apply_trail_length();
+            }
+            Msg::ChangeTrailOpacity(opacity) => self.trail_opacity = *opacity,
+            Msg::ToggleRecording => self.toggle_recording(),
+            Msg::EncodeFrame => self.encode_frame(),
+            Msg::ChangeRecordFrames(frames) => self.record_frames = *frames,
+            Msg::ChangeRecordFps(fps) => self.record_fps = *fps,
+            Msg::ChangeRecordScale(scale) => self.record_scale = *scale,
+            Msg::ChangeRenderer(kind) => {
+                let (kind, renderer) = kind.create();
+                self.renderer_kind = kind;
+                self.renderer = RefCell::new(renderer);
             }
             Msg::TogglePanel => {
                 info!("Toggled panel");
                 self.settings_panel_shown = !self.settings_panel_shown;
             }
+            Msg::ToggleHelp => self.help_shown = !self.help_shown,
             Msg::TogglePredator(me) => {
-                let new = Vector2::new(me.client_x() as f64, me.client_y() as f64);
-                let new_pos = Point2::origin() + new;
-                let delete_idx = self
-                    .boids
-                    .predators
-                    .iter()
-                    .enumerate()
-                    .skip(1) // Do not consider the mouse follower
-                    .filter(|(_, pred)| {
-                        let pred_pos = Point2::origin() + *pred;
-                        na::distance_squared(&new_pos, &pred_pos) <= PREDATOR_CLICK_RADIUS_SQUARED
-                    })
-                    .map(|(idx, _)| idx)
-                    .next();
-                if let Some(idx) = delete_idx {
-                    self.boids.predators.remove(idx);
+                let screen = Vector2::new(me.client_x() as f64, me.client_y() as f64);
+                if self.suppress_click {
+                    self.suppress_click = false;
+                } else if me.shift_key() {
+                    self.inspected = self.boid_at(screen);
                 } else {
-                    self.boids.predators.push(new);
+                    self.toggle_predator(self.camera.to_world(screen));
+                }
+            }
+            Msg::Touch(event) => {
+                // Prevent scrolling and the emulated mouse events
+                event.prevent_default();
+                let update = self.touches.handle(event, util::performance().now());
+                if let [a, b] = update.active[..] {
+                    // Two fingers pinch and pan the view instead of chasing boids
+                    if let Some((last_a, last_b)) = self.pinch {
+                        let factor = (a - b).norm() / (last_a - last_b).norm().max(1.0);
+                        self.camera.pan((a + b - last_a - last_b) / 2.0);
+                        self.camera.zoom_at((a + b) / 2.0, factor);
+                    }
+                    self.pinch = Some((a, b));
+                    self.boids.touches.clear();
+                } else {
+                    self.pinch = None;
+                    self.boids.touches = update
+                        .active
+                        .iter()
+                        .map(|screen| self.camera.to_world(*screen))
+                        .collect();
+                }
+                for screen in update.taps {
+                    self.toggle_predator(self.camera.to_world(screen));
                 }
             }
             Msg::ChangeAlignRadius(radius) => self.boids.align_radius_squared = radius.powf(2.0),
@@ -125,18 +491,103 @@ impl Component for Model {
             Msg::ChangeCohesionFactor(factor) => self.boids.cohesion_factor = *factor,
             Msg::ChangeSeperationFactor(factor) => self.boids.seperation_factor = *factor,
             Msg::ChangeAngstFactor(factor) => self.boids.angst_factor = *factor,
+            Msg::ChangeTargetFps(fps) => {
+                let target_fps = Some(*fps).filter(|fps| *fps > 0.0);
+                self.quality.set_target_fps(&mut self.boids, target_fps)
+            }
             Msg::ChangeNrOfBoids(number) => util::change_number_of_boids(&mut self.boids, *number),
+            Msg::ApplyPreset(preset) => {
+                preset.apply(&mut self.boids);
+                self.inspected = None;
+                self.preset = Some(*preset);
+                if let Some(kind) = preset.theme() {
+                    self.theme_settings.kind = kind;
+                    self.apply_theme();
+                }
+            }
             Msg::ToggleDebugMode => self.boids.debug_mode = !self.boids.debug_mode,
-            Msg::ScatterBoids => self.boids.scatter(),
+            Msg::ScatterBoids => {
+                self.boids.scatter();
+                self.inspected = None;
+            }
             Msg::ToggleSpecialMode => self.special_mode = !self.special_mode,
+            Msg::NextColorMode => self.color_mode = self.color_mode.next(),
+            Msg::ChangeColorMode(mode) => self.color_mode = *mode,
+            Msg::ChangeShape(shape) => self.appearance.shape = *shape,
+            Msg::ChangeBoidScale(scale) => {
+                self.appearance.scale = scale.clamp(MIN_BOID_SCALE, MAX_BOID_SCALE)
+            }
+            Msg::ChangeSpriteUrl(url) => self.set_sprite_url(url.clone()),
+            Msg::ChangeSpriteFile(file) => match Url::create_object_url_with_blob(file) {
+                Ok(url) => self.set_sprite_url(url),
+                Err(why) => error!("Could not load {}: {:?}", file.name(), why),
+            },
+            Msg::SpriteLoaded => self.sprite_error = None,
+            Msg::SpriteFailed => {
+                let why = if self.sprite_url.starts_with("blob:") {
+                    "The file is not an image"
+                } else {
+                    // Images are requested with CORS, so that they can be exported
+                    "The image could not be loaded, the server may not allow other sites to use it"
+                };
+                error!("Failed to load sprite {}", self.sprite_url);
+                self.sprite_error = Some(String::from(why));
+            }
+            Msg::ToggleSpriteRotation => {
+                self.appearance.rotate_sprite = !self.appearance.rotate_sprite
+            }
+            Msg::ChangeDetail(detail) => self.detail = *detail,
+            Msg::ToggleHeatmap => self.show_heatmap = !self.show_heatmap,
+            Msg::ToggleVelocityField => self.show_velocity_field = !self.show_velocity_field,
+            Msg::StopInspecting => self.inspected = None,
+            Msg::ToggleCompactUrl => self.compact_url = !self.compact_url,
         }
         match msg {
-            Msg::Tick
+            Msg::Frame(_)
+            | Msg::VisibilityChanged
+            | Msg::ColorSchemeChanged
+            | Msg::KeyDown(_)
+            | Msg::TogglePause
+            | Msg::StepOnce
+            | Msg::ChangeTimeScale(_)
             | Msg::TogglePanel
+            | Msg::ToggleHelp
             | Msg::ToggleDebugMode
             | Msg::ToggleSpecialMode
+            | Msg::NextColorMode
+            | Msg::ChangeColorMode(_)
+            | Msg::ChangeShape(_)
+            | Msg::ChangeBoidScale(_)
+            | Msg::ChangeSpriteUrl(_)
+            | Msg::ChangeSpriteFile(_)
+            | Msg::SpriteLoaded
+            | Msg::SpriteFailed
+            | Msg::ToggleSpriteRotation
+            | Msg::ChangeDetail(_)
+            | Msg::ToggleHeatmap
+            | Msg::ToggleVelocityField
+            | Msg::StopInspecting
+            | Msg::ChangeTargetFps(_)
             | Msg::MouseMoved(_)
+            | Msg::MouseDown(_)
+            | Msg::MouseUp
+            | Msg::Wheel(_)
+            | Msg::ResetCamera
+            | Msg::Resized(_)
+            | Msg::ChangeResizePolicy(_)
+            | Msg::ChangeRenderer(_)
+            | Msg::ExportSvg
+            | Msg::ChangeTrailMode(_)
+            | Msg::ChangeTrailLength(_)
+            | Msg::ChangeTrailOpacity(_)
+            | Msg::ToggleRecording
+            | Msg::EncodeFrame
+            | Msg::ChangeRecordFrames(_)
+            | Msg::ChangeRecordFps(_)
+            | Msg::ChangeRecordScale(_)
+            | Msg::ChangeWorldScale(_)
             | Msg::TogglePredator(_)
+            | Msg::Touch(_)
             | Msg::ScatterBoids => {}
             Msg::ChangeAlignRadius(_)
             | Msg::ChangeCohesionRadius(_)
@@ -148,9 +599,36 @@ impl Component for Model {
             | Msg::ChangeAlignFactor(_)
             | Msg::ChangeCohesionFactor(_)
             | Msg::ChangeSeperationFactor(_)
-            | Msg::ChangeAngstFactor(_) => util::update_url(&self.boids.to_url_suffix()),
+            | Msg::ChangeAngstFactor(_)
+            | Msg::ApplyPreset(_)
+            | Msg::ChangeTheme(_)
+            | Msg::ChangeCustomBackground(_)
+            | Msg::ChangeCustomBoidColor(_)
+            | Msg::ChangeCustomPredatorColor(_)
+            | Msg::ToggleCompactUrl => self.update_url(),
         }
-        true
+        // Boids are removed from the end, by fewer boids or the adaptive quality.
+        // Forget removed boids, before another one takes their index
+        if self
+            .inspected
+            .is_some_and(|idx| idx >= self.boids.boids.len())
+        {
+            self.inspected = None;
+        }
+        // Frames draw on the canvas themselves and input on the canvas only
+        // changes what the next frame draws, neither changes the html
+        !matches!(
+            msg,
+            Msg::Frame(_)
+                | Msg::EncodeFrame
+                | Msg::VisibilityChanged
+                | Msg::MouseMoved(_)
+                | Msg::MouseDown(_)
+                | Msg::MouseUp
+                | Msg::Wheel(_)
+                | Msg::Touch(_)
+                | Msg::TogglePredator(_)
+        )
     }
 
     fn change(&mut self, _props: Self::Properties) -> ShouldRender {
@@ -163,52 +641,102 @@ impl Component for Model {
     fn view(&self) -> Html {
         html! {
             <>
+                <canvas id=GL_CANVAS_ID></canvas>
                 <canvas id=CANVAS_ID onmousemove=self.link.callback(Msg::MouseMoved)
-                                     onclick=self.link.callback(Msg::TogglePredator)>
+                                     onclick=self.link.callback(Msg::TogglePredator)
+                                     onmousedown=self.link.callback(Msg::MouseDown)
+                                     onmouseup=self.link.callback(|_| Msg::MouseUp)
+                                     onmouseleave=self.link.callback(|_| Msg::MouseUp)
+                                     onwheel=self.link.callback(Msg::Wheel)
+                                     ontouchstart=self.link.callback(Msg::Touch)
+                                     ontouchmove=self.link.callback(Msg::Touch)
+                                     ontouchend=self.link.callback(Msg::Touch)
+                                     ontouchcancel=self.link.callback(Msg::Touch)>
                 </canvas>
                 <button id="toggle-panel" onclick=self.link.callback(|_| Msg::TogglePanel)>
                 </button>
                 { self.display_settings_panel() }
+                { self.display_help() }
                 <img id=QR_CODE_ID src=QR_CODE_LOCATION />
+                <img id=SPRITE_ID
+                     src={self.sprite_url.clone()}
+                     crossorigin="anonymous"
+                     onload=self.link.callback(|_| Msg::SpriteLoaded)
+                     onerror=self.link.callback(|_| Msg::SpriteFailed) />
             </>
         }
     }
+}
 
-    fn rendered(&mut self, _first_render: bool) {
-        self.render();
-    }
+/// Callback for a `<select>` whose option values parse into `$ty`.
+macro_rules! select {
+    ($msg:path, $ty:ty) => {
+        |raw: ChangeData| {
+            if let ChangeData::Select(select) = raw {
+                $msg(select.value().parse::<$ty>().unwrap())
+            } else {
+                error!("ChangeData sucks...");
+                panic!("ChangeData sucks...");
+            }
+        }
+    };
+}
+
+/// Batch callback for an `<input>` whose value parses into the argument of `$msg`.
+///
+/// Values that don't parse, like an emptied number field, are ignored.
+macro_rules! value {
+    ($msg:path) => {
+        |raw: ChangeData| {
+            if let ChangeData::Value(raw) = raw {
+                raw.parse().ok().map($msg).into_iter().collect()
+            } else {
+                error!("ChangeData sucks...");
+                panic!("ChangeData sucks...");
+            }
+        }
+    };
 }
 
 impl Model {
     fn display_settings_panel(&self) -> Html {
-        macro_rules! change {
-            ($msg:ident) => {
-                self.link.callback(|raw: ChangeData| {
-                    if let ChangeData::Value(raw) = raw {
-                        Msg::$msg(raw.parse().unwrap())
-                    } else {
-                        error!("ChangeData sucks...");
-                        panic!("ChangeData sucks...");
-                    }
-                })
-            };
-        }
         macro_rules! click {
             ($msg:ident) => {
                 self.link.callback(|_| Msg::$msg)
             };
         }
+        let select_preset = self.link.callback(select!(Msg::ApplyPreset, Preset));
+        let select_resize_policy = self
+            .link
+            .callback(select!(Msg::ChangeResizePolicy, ResizePolicy));
+        let select_theme = self.link.callback(select!(Msg::ChangeTheme, ThemeKind));
+        let select_color_mode = self.link.callback(select!(Msg::ChangeColorMode, ColorMode));
+        let select_trail_mode = self.link.callback(select!(Msg::ChangeTrailMode, TrailMode));
+        let select_renderer = self
+            .link
+            .callback(select!(Msg::ChangeRenderer, RendererKind));
         if self.settings_panel_shown {
             html! {
                 <div id="settings-panel">
+                    <div>
+                        <label for="preset">{ "Preset" }</label>
+                        <select id="preset" name="preset" onchange={select_preset}>
+                            <option value="" selected=true disabled=true>{ "Choose..." }</option>
+                            { for Preset::ALL.iter().map(|preset| html! {
+                                <option value={preset.name()}>{ preset.label() }</option>
+                            }) }
+                        </select>
+                    </div>
                     <div>
                         <label for="nr-of-boids">{ "Nr of Boids" }</label>
                         <input type="range"
                                id="nr-of-boids"
                                name="nr-of-boids"
-                               min="0" max="4000"
-                               value={self.boids.boids.len()}
-                               onchange={change!(ChangeNrOfBoids)}
+                               min={params::NR_OF_BOIDS.min}
+                               max={params::NR_OF_BOIDS.max}
+                               step={params::NR_OF_BOIDS.step}
+                               value={self.boids.boids.len() + self.boids.suspended.len()}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeNrOfBoids))}
                         />
                     </div>
                     <div>
@@ -216,9 +744,11 @@ impl Model {
                         <input type="range"
                                id="align-radius"
                                name="align-radius"
-                               min="0" max="400"
+                               min={params::ALIGN_RADIUS.min}
+                               max={params::ALIGN_RADIUS.max}
+                               step={params::ALIGN_RADIUS.step}
                                value={self.boids.align_radius_squared.sqrt()}
-                               onchange={change!(ChangeAlignRadius)}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeAlignRadius))}
                         />
                     </div>
                     <div>
@@ -226,9 +756,11 @@ impl Model {
                         <input type="range"
                                id="cohesion-radius"
                                name="cohesion-radius"
-                               min="0" max="400"
+                               min={params::COHESION_RADIUS.min}
+                               max={params::COHESION_RADIUS.max}
+                               step={params::COHESION_RADIUS.step}
                                value={self.boids.cohesion_radius_squared.sqrt()}
-                               onchange={change!(ChangeCohesionRadius)}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeCohesionRadius))}
                         />
                     </div>
                     <div>
@@ -236,9 +768,11 @@ impl Model {
                         <input type="range"
                                id="seperation-radius"
                                name="seperation-radius"
-                               min="0" max="400"
+                               min={params::SEPERATION_RADIUS.min}
+                               max={params::SEPERATION_RADIUS.max}
+                               step={params::SEPERATION_RADIUS.step}
                                value={self.boids.seperation_radius_squared.sqrt()}
-                               onchange={change!(ChangeSeperationRadius)}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeSeperationRadius))}
                         />
                     </div>
                     <div>
@@ -246,9 +780,11 @@ impl Model {
                         <input type="range"
                                id="angst-radius"
                                name="angst-radius"
-                               min="0" max="400"
+                               min={params::ANGST_RADIUS.min}
+                               max={params::ANGST_RADIUS.max}
+                               step={params::ANGST_RADIUS.step}
                                value={self.boids.angst_radius_squared.sqrt()}
-                               onchange={change!(ChangeAngstRadius)}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeAngstRadius))}
                         />
                     </div>
                     <div>
@@ -256,9 +792,11 @@ impl Model {
                         <input type="range"
                                id="align-factor"
                                name="align-factor"
-                               min="0" max="10" step="0.1"
+                               min={params::ALIGN_FACTOR.min}
+                               max={params::ALIGN_FACTOR.max}
+                               step={params::ALIGN_FACTOR.step}
                                value={self.boids.align_factor}
-                               onchange={change!(ChangeAlignFactor)}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeAlignFactor))}
                         />
                     </div>
                     <div>
@@ -266,9 +804,11 @@ impl Model {
                         <input type="range"
                                id="cohesion-factor"
                                name="cohesion-factor"
-                               min="0" max="10" step="0.1"
+                               min={params::COHESION_FACTOR.min}
+                               max={params::COHESION_FACTOR.max}
+                               step={params::COHESION_FACTOR.step}
                                value={self.boids.cohesion_factor}
-                               onchange={change!(ChangeCohesionFactor)}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeCohesionFactor))}
                         />
                     </div>
                     <div>
@@ -276,9 +816,11 @@ impl Model {
                         <input type="range"
                                id="seperation-factor"
                                name="seperation-factor"
-                               min="0" max="10" step="0.1"
+                               min={params::SEPERATION_FACTOR.min}
+                               max={params::SEPERATION_FACTOR.max}
+                               step={params::SEPERATION_FACTOR.step}
                                value={self.boids.seperation_factor}
-                               onchange={change!(ChangeSeperationFactor)}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeSeperationFactor))}
                         />
                     </div>
                     <div>
@@ -286,9 +828,11 @@ impl Model {
                         <input type="range"
                                id="angst-factor"
                                name="angst-factor"
-                               min="0" max="20000"
+                               min={params::ANGST_FACTOR.min}
+                               max={params::ANGST_FACTOR.max}
+                               step={params::ANGST_FACTOR.step}
                                value={self.boids.angst_factor}
-                               onchange={change!(ChangeAngstFactor)}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeAngstFactor))}
                         />
                     </div>
                     <div>
@@ -296,9 +840,11 @@ impl Model {
                         <input type="range"
                                id="max-steer"
                                name="max-steer"
-                               min="0" max="50" step="0.1"
+                               min={params::MAX_STEER.min}
+                               max={params::MAX_STEER.max}
+                               step={params::MAX_STEER.step}
                                value={self.boids.max_steer}
-                               onchange={change!(ChangeMaxSteer)}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeMaxSteer))}
                         />
                     </div>
                     <div>
@@ -306,17 +852,158 @@ impl Model {
                         <input type="range"
                                id="max-speed"
                                name="max-speed"
-                               min="0" max="500"
+                               min={params::MAX_SPEED.min}
+                               max={params::MAX_SPEED.max}
+                               step={params::MAX_SPEED.step}
                                value={self.boids.max_speed}
-                               onchange={change!(ChangeMaxSpeed)}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeMaxSpeed))}
+                        />
+                    </div>
+                    <div>
+                        <label for="target-fps">{ "Target FPS (0 = off)" }</label>
+                        <input type="range"
+                               id="target-fps"
+                               name="target-fps"
+                               min="0" max="120" step="5"
+                               value={self.quality.target_fps.unwrap_or(0.0)}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeTargetFps))}
+                        />
+                    </div>
+                    <div>
+                        <label for="world-scale">{ "World Size" }</label>
+                        <input type="range"
+                               id="world-scale"
+                               name="world-scale"
+                               min="1" max="4" step="0.5"
+                               value={self.world_scale}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeWorldScale))}
+                        />
+                    </div>
+                    <div>
+                        <label for="theme">{ "Theme" }</label>
+                        <select id="theme" name="theme" onchange={select_theme}>
+                            { for ThemeKind::ALL.iter().map(|kind| html! {
+                                <option value={kind.name()}
+                                        selected={*kind == self.theme_settings.kind}>
+                                    { kind.label() }
+                                </option>
+                            }) }
+                        </select>
+                    </div>
+                    { self.display_custom_colors() }
+                    <div>
+                        <label for="color-mode">{ "Colour By" }</label>
+                        <select id="color-mode" name="color-mode" onchange={select_color_mode}>
+                            { for ColorMode::ALL.iter().map(|mode| html! {
+                                <option value={mode.name()}
+                                        selected={*mode == self.color_mode}>
+                                    { mode.label() }
+                                </option>
+                            }) }
+                        </select>
+                    </div>
+                    { self.display_appearance() }
+                    <div>
+                        <label for="trail-mode">{ "Trails" }</label>
+                        <select id="trail-mode" name="trail-mode" onchange={select_trail_mode}>
+                            { for TrailMode::ALL.iter().map(|mode| html! {
+                                <option value={mode.name()}
+                                        selected={*mode == self.trail_mode}>
+                                    { mode.label() }
+                                </option>
+                            }) }
+                        </select>
+                    </div>
+                    <div>
+                        <label for="trail-length">{ "Trail Length" }</label>
+                        <input type="range"
+                               id="trail-length"
+                               name="trail-length"
+                               min="2" max="100" step="1"
+                               value={self.trail_length}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeTrailLength))}
+                        />
+                    </div>
+                    <div>
+                        <label for="trail-opacity">{ "Trail Opacity" }</label>
+                        <input type="range"
+                               id="trail-opacity"
+                               name="trail-opacity"
+                               min="0" max="0.95" step="0.05"
+                               value={self.trail_opacity}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeTrailOpacity))}
+                        />
+                    </div>
+                    <div>
+                        <label for="renderer">{ "Renderer" }</label>
+                        <select id="renderer" name="renderer" onchange={select_renderer}>
+                            { for RendererKind::ALL.iter().map(|kind| html! {
+                                <option value={kind.name()}
+                                        selected={*kind == self.renderer_kind}>
+                                    { kind.label() }
+                                </option>
+                            }) }
+                        </select>
+                    </div>
+                    <div>
+                        <label for="resize-policy">{ "On Resize" }</label>
+                        <select id="resize-policy" name="resize-policy" onchange={select_resize_policy}>
+                            { for ResizePolicy::ALL.iter().map(|policy| html! {
+                                <option value={policy.name()}
+                                        selected={*policy == self.resize_policy}>
+                                    { policy.label() }
+                                </option>
+                            }) }
+                        </select>
+                    </div>
+                    <div>
+                        <button id="reset-camera"
+                                onclick={click!(ResetCamera)}>
+                            { "Reset View" }
+                        </button>
+                    </div>
+                    <div>
+                        <label for="time-scale">{ "Time Scale" }</label>
+                        <input type="range"
+                               id="time-scale"
+                               name="time-scale"
+                               min={MIN_TIME_SCALE}
+                               max={MAX_TIME_SCALE}
+                               step={TIME_SCALE_STEP}
+                               value={self.clock.time_scale}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeTimeScale))}
                         />
                     </div>
+                    <div>
+                        <button id="toggle-pause"
+                                onclick={click!(TogglePause)}>
+                            { if self.paused { "Resume" } else { "Pause" } }
+                        </button>
+                    </div>
+                    <div>
+                        <button id="step-once"
+                                onclick={click!(StepOnce)}>
+                            { "Step" }
+                        </button>
+                    </div>
                     <div>
                         <button id="toggle-debug"
                                 onclick={click!(ToggleDebugMode)}>
                             { "Toggle Debug" }
                         </button>
                     </div>
+                    <div>
+                        <button id="toggle-heatmap"
+                                onclick={click!(ToggleHeatmap)}>
+                            { if self.show_heatmap { "Hide Heatmap" } else { "Density Heatmap" } }
+                        </button>
+                    </div>
+                    <div>
+                        <button id="toggle-velocity-field"
+                                onclick={click!(ToggleVelocityField)}>
+                            { if self.show_velocity_field { "Hide Velocity Field" } else { "Velocity Field" } }
+                        </button>
+                    </div>
                     <div>
                         <button id="toggle-special-mode"
                                 onclick={click!(ToggleSpecialMode)}>
@@ -329,14 +1016,488 @@ impl Model {
                             { "Scatter!" }
                         </button>
                     </div>
+                    <div>
+                        <button id="export-svg"
+                                onclick={click!(ExportSvg)}>
+                            { "Export SVG" }
+                        </button>
+                    </div>
+                    <div>
+                        <label for="record-frames">{ "Recording Frames" }</label>
+                        <input type="range"
+                               id="record-frames"
+                               name="record-frames"
+                               min="10" max="500" step="10"
+                               value={self.record_frames}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeRecordFrames))}
+                        />
+                    </div>
+                    <div>
+                        <label for="record-fps">{ "Recording FPS" }</label>
+                        <input type="range"
+                               id="record-fps"
+                               name="record-fps"
+                               min="5" max="50" step="5"
+                               value={self.record_fps}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeRecordFps))}
+                        />
+                    </div>
+                    <div>
+                        <label for="record-scale">{ "Recording Size" }</label>
+                        <input type="range"
+                               id="record-scale"
+                               name="record-scale"
+                               min="0.1" max="1" step="0.1"
+                               value={self.record_scale}
+                               onchange={self.link.batch_callback(value!(Msg::ChangeRecordScale))}
+                        />
+                    </div>
+                    <div>
+                        <button id="toggle-recording"
+                                onclick={click!(ToggleRecording)}>
+                            { if self.recording.is_some() { "Stop Recording" } else { "Record GIF" } }
+                        </button>
+                    </div>
+                    <div>
+                        <button id="toggle-help"
+                                onclick={click!(ToggleHelp)}>
+                            { "Keyboard Shortcuts" }
+                        </button>
+                    </div>
+                    <div>
+                        <button id="toggle-compact-url"
+                                onclick={click!(ToggleCompactUrl)}>
+                            { if self.compact_url { "Readable URL" } else { "Compact URL" } }
+                        </button>
+                    </div>
+                </div>
+            }
+        } else {
+            html! {}
+        }
+    }
+    /// Advance the simulation by a single step.
+    fn step(&mut self) {
+        util::update(&mut self.boids, SIMULATION_STEP, &mut self.profiler);
+    }
+    fn handle_key(&mut self, event: &KeyboardEvent) {
+        if event.ctrl_key() || event.alt_key() || event.meta_key() {
+            return;
+        }
+        // Keys typed into the panel belong to its controls
+        let target = event
+            .target()
+            .and_then(|target| target.dyn_into::<HtmlElement>().ok());
+        if let Some(target) = target {
+            let tag = target.tag_name().to_ascii_lowercase();
+            let control = matches!(tag.as_str(), "input" | "textarea" | "select" | "button");
+            if control || target.is_content_editable() {
+                return;
+            }
+        }
+        let binding = match keys::binding_for(&event.key()) {
+            Some(binding) => binding,
+            None => return,
+        };
+        let msg = match &binding.action {
+            Action::Send(msg) => msg(),
+            Action::TimeScale(delta) => Msg::ChangeTimeScale(self.clock.time_scale + delta),
+            Action::Nudge(param, delta, msg) => msg(param.clamp((param.get)(&self.boids) + delta)),
+        };
+        event.prevent_default();
+        self.link.send_message(msg);
+    }
+    /// Colour pickers for the custom theme, if it is selected.
+    fn display_custom_colors(&self) -> Html {
+        let custom = &self.theme_settings.custom;
+        if self.theme_settings.kind != ThemeKind::Custom {
+            return html! {};
+        }
+        html! {
+            <>
+                <div>
+                    <label for="custom-background">{ "Background Colour" }</label>
+                    <input type="color"
+                           id="custom-background"
+                           name="custom-background"
+                           value={custom.background.clone()}
+                           onchange={self.link.batch_callback(value!(Msg::ChangeCustomBackground))}
+                    />
+                </div>
+                <div>
+                    <label for="custom-boid">{ "Boid Colour" }</label>
+                    <input type="color"
+                           id="custom-boid"
+                           name="custom-boid"
+                           value={custom.boid.clone()}
+                           onchange={self.link.batch_callback(value!(Msg::ChangeCustomBoidColor))}
+                    />
+                </div>
+                <div>
+                    <label for="custom-predator">{ "Predator Colour" }</label>
+                    <input type="color"
+                           id="custom-predator"
+                           name="custom-predator"
+                           value={custom.predator.clone()}
+                           onchange={self.link.batch_callback(value!(Msg::ChangeCustomPredatorColor))}
+                    />
+                </div>
+            </>
+        }
+    }
+    fn display_appearance(&self) -> Html {
+        let select_shape = self.link.callback(select!(Msg::ChangeShape, Shape));
+        let select_detail = self.link.callback(select!(Msg::ChangeDetail, Detail));
+        let change_scale = self.link.batch_callback(value!(Msg::ChangeBoidScale));
+        html! {
+            <>
+                <div>
+                    <label for="shape">{ "Shape" }</label>
+                    <select id="shape" name="shape" onchange={select_shape}>
+                        { for Shape::ALL.iter().map(|shape| html! {
+                            <option value={shape.name()}
+                                    selected={*shape == self.appearance.shape}>
+                                { shape.label() }
+                            </option>
+                        }) }
+                    </select>
+                </div>
+                <div>
+                    <label for="boid-scale">{ "Boid Size" }</label>
+                    <input type="range"
+                           id="boid-scale"
+                           name="boid-scale"
+                           min={MIN_BOID_SCALE} max={MAX_BOID_SCALE} step="0.1"
+                           value={self.appearance.scale}
+                           onchange={change_scale}
+                    />
+                </div>
+                { self.display_sprite_settings() }
+                <div>
+                    <label for="detail">{ "Detail" }</label>
+                    <select id="detail" name="detail" onchange={select_detail}>
+                        { for Detail::ALL.iter().map(|detail| html! {
+                            <option value={detail.name()}
+                                    selected={*detail == self.detail}>
+                                { detail.label() }
+                            </option>
+                        }) }
+                    </select>
+                </div>
+            </>
+        }
+    }
+    fn display_sprite_settings(&self) -> Html {
+        if self.appearance.shape != Shape::Sprite {
+            return html! {};
+        }
+        let change_url = self.link.batch_callback(value!(Msg::ChangeSpriteUrl));
+        let choose_file = self.link.batch_callback(|raw: ChangeData| {
+            if let ChangeData::Files(files) = raw {
+                files
+                    .get(0)
+                    .map(Msg::ChangeSpriteFile)
+                    .into_iter()
+                    .collect()
+            } else {
+                error!("ChangeData sucks...");
+                panic!("ChangeData sucks...");
+            }
+        });
+        // Object urls of files are meaningless to the user
+        let url = if self.sprite_url.starts_with("blob:") {
+            ""
+        } else {
+            &self.sprite_url
+        };
+        html! {
+            <>
+                <div>
+                    <label for="sprite-url">{ "Image URL" }</label>
+                    <input type="url"
+                           id="sprite-url"
+                           name="sprite-url"
+                           value={url}
+                           onchange={change_url}
+                    />
+                </div>
+                { for self.sprite_error.iter().map(|why| html! {
+                    <div class="error">{ why }</div>
+                }) }
+                <div>
+                    <label for="sprite-file">{ "Image File" }</label>
+                    <input type="file"
+                           id="sprite-file"
+                           name="sprite-file"
+                           accept="image/*"
+                           onchange={choose_file}
+                    />
+                </div>
+                <div>
+                    <button id="toggle-sprite-rotation"
+                            onclick={self.link.callback(|_| Msg::ToggleSpriteRotation)}>
+                        { if self.appearance.rotate_sprite { "Fixed Image" } else { "Rotate Image" } }
+                    </button>
+                </div>
+            </>
+        }
+    }
+    /// Use a new image as sprite, releasing the file of the previous one.
+    fn set_sprite_url(&mut self, url: String) {
+        if self.sprite_url.starts_with("blob:") {
+            if let Err(why) = Url::revoke_object_url(&self.sprite_url) {
+                error!("Could not release the previous image: {:?}", why);
+            }
+        }
+        self.sprite_url = url;
+        self.sprite_error = None;
+    }
+    /// The appearance boids are drawn with, the QR code of the
+    /// special mode is always upright.
+    pub fn active_appearance(&self) -> Appearance {
+        if self.special_mode {
+            Appearance {
+                rotate_sprite: false,
+                ..self.appearance
+            }
+        } else {
+            self.appearance
+        }
+    }
+    /// The level of detail to draw the boids with in this frame.
+    pub fn detail(&self) -> Detail {
+        let screen_scale = self.camera.zoom * self.appearance.scale;
+        self.detail.resolve(
+            self.boids.boids.len(),
+            screen_scale,
+            self.quality.low_detail,
+        )
+    }
+    /// The image to draw instead of every boid, once it is loaded.
+    fn sprite(&self) -> Option<HtmlImageElement> {
+        if self.special_mode {
+            util::get_loaded_image(QR_CODE_ID)
+        } else if self.appearance.shape == Shape::Sprite {
+            util::get_loaded_image(SPRITE_ID)
+        } else {
+            None
+        }
+    }
+    /// Resolve the selected theme, after it or the colour scheme changed.
+    fn apply_theme(&mut self) {
+        self.theme = self.theme_settings.resolve(self.prefers_dark);
+        util::set_page_theme(self.theme.is_dark());
+    }
+    fn update_url(&self) {
+        let suffix = if self.compact_url {
+            self.boids.to_compact_url_suffix(self.preset)
+        } else {
+            self.boids.to_url_suffix(self.preset)
+        };
+        let base_theme = self
+            .preset
+            .and_then(|preset| preset.theme())
+            .unwrap_or(ThemeKind::System);
+        util::update_url(&(suffix + &self.theme_settings.to_url_query(base_theme)));
+    }
+    fn display_help(&self) -> Html {
+        if self.help_shown {
+            html! {
+                <div id="help">
+                    <table>
+                        { for keys::BINDINGS.iter().map(|binding| html! {
+                            <tr>
+                                <td><kbd>{ binding.label }</kbd></td>
+                                <td>{ binding.description }</td>
+                            </tr>
+                        }) }
+                        <tr>
+                            <td><kbd>{ "Shift" }</kbd>{ " + Click" }</td>
+                            <td>{ "Inspect a boid" }</td>
+                        </tr>
+                    </table>
                 </div>
             }
         } else {
             html! {}
         }
     }
-    pub fn render(&self) {
-        let boids = &self.boids;
+    /// Index of the boid nearest to the `screen` position, if it is close enough.
+    fn boid_at(&self, screen: Vector2<f64>) -> Option<usize> {
+        let pos = self.camera.to_world(screen);
+        let radius = INSPECT_RADIUS / self.camera.zoom;
+        self.boids
+            .boids
+            .iter()
+            .map(|boid| (boid.pos - pos).norm_squared())
+            .enumerate()
+            .filter(|(_, dist)| *dist <= radius.powf(2.0))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(idx, _)| idx)
+    }
+    /// Index of the boid selected for inspection, if any.
+    pub fn inspected(&self) -> Option<usize> {
+        self.inspected
+    }
+    /// Remove the predator at `pos` or place a new one if there is none.
+    fn toggle_predator(&mut self, pos: Vector2<f64>) {
+        let new_pos = Point2::origin() + pos;
+        let delete_idx = self
+            .boids
+            .predators
+            .iter()
+            .enumerate()
+            .skip(1) // Do not consider the mouse follower
+            .filter(|(_, pred)| {
+                let pred_pos = Point2::origin() + *pred;
+                na::distance_squared(&new_pos, &pred_pos) <= PREDATOR_CLICK_RADIUS_SQUARED
+            })
+            .map(|(idx, _)| idx)
+            .next();
+        if let Some(idx) = delete_idx {
+            self.boids.predators.remove(idx);
+        } else {
+            self.boids.predators.push(pos);
+        }
+    }
+    /// Only remember past positions if they are drawn.
+    fn apply_trail_length(&mut self) {
+        self.boids.trail_length = if self.trail_mode == TrailMode::Lines {
+            self.trail_length
+        } else {
+            0
+        };
+    }
+    /// Start a recording with the current settings, or stop the running
+    /// one and offer it as download.
+    fn toggle_recording(&mut self) {
+        match self.recording.take() {
+            Some(recording) => {
+                if recording.frames() == 0 {
+                    return;
+                }
+                match recording.finish() {
+                    Ok(gif) => util::download("boids.gif", "image/gif", &gif),
+                    Err(why) => error!("Failed to encode recording: {}", why),
+                }
+            }
+            None => {
+                let width = (self.camera.view.0 * self.record_scale).round() as u16;
+                let height = (self.camera.view.1 * self.record_scale).round() as u16;
+                match Recording::new(width, height, self.record_fps) {
+                    Ok(recording) => self.recording = Some(recording),
+                    Err(why) => error!("Failed to start recording: {}", why),
+                }
+            }
+        }
+    }
+    /// Add the frame just drawn to the running recording, if it is time to.
+    /// `simulated` are the milliseconds simulated for this frame, so the
+    /// recording plays at simulation speed however fast frames are drawn.
+    fn capture_frame(&mut self, simulated: f64) {
+        let recording = match &mut self.recording {
+            Some(recording) => recording,
+            None => return,
+        };
+        if !recording.frame_due(simulated) {
+            return;
+        }
+        let (width, height) = (recording.width as u32, recording.height as u32);
+        recording.push(util::capture_canvases(
+            &[GL_CANVAS_ID, CANVAS_ID],
+            width,
+            height,
+        ));
+        while recording.pending() > MAX_PENDING_RECORD_FRAMES {
+            if let Err(why) = recording.encode_next() {
+                error!("Failed to record frame: {}", why);
+            }
+        }
+        if recording.frames() >= self.record_frames {
+            self.link.send_message(Msg::ToggleRecording);
+        } else if self._encode_task.is_none() {
+            self.schedule_encoding();
+        }
+    }
+    /// Encode the next captured frame once the current frame is done.
+    fn schedule_encoding(&mut self) {
+        let callback = self.link.callback(|_| Msg::EncodeFrame);
+        self._encode_task = Some(TimeoutService::spawn(Duration::from_millis(0), callback));
+    }
+    /// Encode one captured frame, and schedule the next one if there is any.
+    fn encode_frame(&mut self) {
+        self._encode_task = None;
+        if let Some(recording) = &mut self.recording {
+            if let Err(why) = recording.encode_next() {
+                error!("Failed to record frame: {}", why);
+            }
+            if recording.pending() > 0 {
+                self.schedule_encoding();
+            }
+        }
+    }
+    /// Draw the heatmap and velocity field overlays, if shown.
+    fn draw_fields(&self, ctx: &impl Draw, camera: &Camera) {
+        if !self.show_heatmap && !self.show_velocity_field {
+            return;
+        }
+        let cells = CellField::new(&self.boids);
+        camera.apply(ctx);
+        if self.show_heatmap {
+            field::draw_heatmap(ctx, &cells);
+        }
+        if self.show_velocity_field {
+            field::draw_velocity_field(ctx, &cells, &self.boids, &self.theme);
+        }
+        camera.reset(ctx);
+    }
+    /// The current view as SVG document, including the debug overlay in debug mode.
+    fn to_svg(&self) -> String {
+        let mut camera = self.camera.clone();
+        camera.pixel_ratio = 1.0;
+        let svg = Svg::new(camera.view.0, camera.view.1);
+        // Object urls and relative urls do not work outside of the page
+        let sprite = self.sprite().and_then(|image| util::image_data_url(&image));
+        renderer::draw_background(
+            &svg,
+            &self.boids,
+            &camera,
+            &self.theme,
+            self.special_mode,
+            1.0,
+        );
+        trails::draw_trails(
+            &svg,
+            &self.boids,
+            &camera,
+            self.trail_opacity,
+            &self.coloring,
+        );
+        renderer::draw_boids(
+            &svg,
+            &self.boids,
+            &camera,
+            &self.coloring,
+            &self.active_appearance(),
+            // Every boid stays a shape in vector graphics
+            Detail::Full,
+            sprite.as_ref(),
+        );
+        renderer::draw_predators(&svg, &self.boids, &camera, &self.theme);
+        self.draw_fields(&svg, &camera);
+        coloring::draw_legend(&svg, &self.coloring, &self.theme, camera.view);
+        if self.boids.debug_mode {
+            camera.apply(&svg);
+            debug::render_debug_info(&svg, self, &camera);
+        }
+        if let Some(idx) = self.inspected() {
+            camera.apply(&svg);
+            debug::render_inspection(&svg, self, &camera, idx);
+        }
+        svg.finish()
+    }
+    pub fn render(&mut self) {
+        let start = profile::now();
         let canvas = document().get_element_by_id(CANVAS_ID).unwrap();
         let canvas: web_sys::HtmlCanvasElement = canvas
             .dyn_into::<web_sys::HtmlCanvasElement>()
@@ -348,27 +1509,47 @@ impl Model {
             .unwrap()
             .dyn_into::<CanvasRenderingContext2d>()
             .unwrap();
-        let qrcode = util::get_qrcode();
-        // Adjust the size
-        canvas.set_width(boids.size.0 as u32);
-        canvas.set_height(boids.size.1 as u32);
-        // Draw the background
-        if self.special_mode {
-            ctx.set_fill_style(&JsValue::from_str("black"));
-        } else {
-            ctx.set_fill_style(&JsValue::from_str(BG_COLOR));
-        }
-        ctx.fill_rect(0.0, 0.0, canvas.width() as f64, canvas.height() as f64);
-        // Draw all the boids
-        ctx.begin_path();
-        ctx.set_fill_style(&JsValue::from_str(BOID_COLOR));
-        for boid in &boids.boids {
-            boid.render(self, &ctx, &qrcode);
+        let sprite = self.sprite();
+        // Adjust the size, but only if necessary. Resizing clears the canvas
+        // and reallocates its backing store
+        self.camera.pixel_ratio = util::get_device_pixel_ratio();
+        let (width, height) = self.camera.backing_size();
+        if canvas.width() != width || canvas.height() != height {
+            canvas.set_width(width);
+            canvas.set_height(height);
         }
-        ctx.fill();
+        self.coloring = Coloring::new(self.color_mode, &self.boids, &self.theme);
+        // Let the active renderer draw the background and the boids
+        self.camera.reset(&ctx);
+        let mut renderer = self.renderer.borrow_mut();
+        renderer.draw_background(self, &ctx);
+        trails::draw_trails(
+            &ctx,
+            &self.boids,
+            &self.camera,
+            self.trail_opacity,
+            &self.coloring,
+        );
+        let background_done = profile::now();
+        renderer.draw_boids(self, &ctx, sprite.as_ref());
+        drop(renderer);
+        self.draw_fields(&ctx, &self.camera);
+        coloring::draw_legend(&ctx, &self.coloring, &self.theme, self.camera.view);
+        let boids_done = profile::now();
         // Draw debug info if necessary
-        if boids.debug_mode {
-            debug::render_debug_info(&ctx, self);
+        if self.boids.debug_mode {
+            self.camera.apply(&ctx);
+            debug::render_debug_info(&ctx, self, &self.camera);
+        }
+        if let Some(idx) = self.inspected() {
+            self.camera.apply(&ctx);
+            debug::render_inspection(&ctx, self, &self.camera, idx);
         }
+        let debug_done = profile::now();
+        self.profiler
+            .record(Phase::Background, background_done - start);
+        self.profiler
+            .record(Phase::Boids, boids_done - background_done);
+        self.profiler.record(Phase::Debug, debug_done - boids_done);
     }
 }
@@ -0,0 +1,177 @@
+use crate::{
+    boids::Boids, presets::Preset, utils as util, DEFAULT_ALIGN_FACTOR, DEFAULT_ALIGN_RADIUS,
+    DEFAULT_ANGST_FACTOR, DEFAULT_ANGST_RADIUS, DEFAULT_COHESION_FACTOR, DEFAULT_COHESION_RADIUS,
+    DEFAULT_MAX_SPEED, DEFAULT_MAX_STEER, DEFAULT_NR_OF_BOIDS, DEFAULT_SEPERATION_FACTOR,
+    DEFAULT_SEPERATION_RADIUS,
+};
+
+/// A tunable parameter of the simulation.
+///
+/// The limits are shared between the sliders in the settings panel
+/// and the validation of values read from the url.
+pub struct Param {
+    /// Key used in the url since version 2
+    pub key: &'static str,
+    /// Key used in the url by version 1
+    pub legacy_key: &'static str,
+    pub min: f64,
+    pub max: f64,
+    pub step: f64,
+    pub default: f64,
+    pub get: fn(&Boids) -> f64,
+    pub set: fn(&mut Boids, f64),
+}
+
+impl Param {
+    /// Clamp `val` into the limits of this parameter.
+    pub fn clamp(&self, val: f64) -> f64 {
+        val.max(self.min).min(self.max)
+    }
+
+    /// The value urls are relative to, the default or the value of `preset`.
+    pub fn base(&self, preset: Option<Preset>) -> f64 {
+        preset.map_or(self.default, |preset| preset.value(self))
+    }
+
+    /// Whether `boids` still have the [`Param::base`] value for this parameter.
+    pub fn is_unchanged(&self, boids: &Boids, preset: Option<Preset>) -> bool {
+        ((self.get)(boids) - self.base(preset)).abs() <= f64::EPSILON
+    }
+}
+
+pub const NR_OF_BOIDS: Param = Param {
+    key: "nr-of-boids",
+    legacy_key: "nr-of-boids",
+    min: 0.0,
+    max: 25000.0,
+    step: 1.0,
+    default: DEFAULT_NR_OF_BOIDS as f64,
+    get: |boids| (boids.boids.len() + boids.suspended.len()) as f64,
+    set: |boids, val| util::change_number_of_boids(boids, val.round() as usize),
+};
+
+pub const ALIGN_RADIUS: Param = Param {
+    key: "align-radius",
+    legacy_key: "align-radius",
+    min: 0.0,
+    max: 400.0,
+    step: 1.0,
+    default: DEFAULT_ALIGN_RADIUS,
+    get: |boids| boids.align_radius_squared.sqrt(),
+    set: |boids, val| boids.align_radius_squared = val.powf(2.0),
+};
+
+pub const COHESION_RADIUS: Param = Param {
+    key: "cohesion-radius",
+    legacy_key: "cohesion-radius",
+    min: 0.0,
+    max: 400.0,
+    step: 1.0,
+    default: DEFAULT_COHESION_RADIUS,
+    get: |boids| boids.cohesion_radius_squared.sqrt(),
+    set: |boids, val| boids.cohesion_radius_squared = val.powf(2.0),
+};
+
+pub const SEPERATION_RADIUS: Param = Param {
+    key: "separation-radius",
+    legacy_key: "seperation-radius",
+    min: 0.0,
+    max: 400.0,
+    step: 1.0,
+    default: DEFAULT_SEPERATION_RADIUS,
+    get: |boids| boids.seperation_radius_squared.sqrt(),
+    set: |boids, val| boids.seperation_radius_squared = val.powf(2.0),
+};
+
+pub const ANGST_RADIUS: Param = Param {
+    key: "angst-radius",
+    legacy_key: "angst-radius",
+    min: 0.0,
+    max: 400.0,
+    step: 1.0,
+    default: DEFAULT_ANGST_RADIUS,
+    get: |boids| boids.angst_radius_squared.sqrt(),
+    set: |boids, val| boids.angst_radius_squared = val.powf(2.0),
+};
+
+pub const MAX_SPEED: Param = Param {
+    key: "max-speed",
+    legacy_key: "max-speed",
+    min: 0.0,
+    max: 500.0,
+    step: 1.0,
+    default: DEFAULT_MAX_SPEED,
+    get: |boids| boids.max_speed,
+    set: |boids, val| boids.max_speed = val,
+};
+
+pub const MAX_STEER: Param = Param {
+    key: "max-steer",
+    legacy_key: "max-steer",
+    min: 0.0,
+    max: 50.0,
+    step: 0.1,
+    default: DEFAULT_MAX_STEER,
+    get: |boids| boids.max_steer,
+    set: |boids, val| boids.max_steer = val,
+};
+
+pub const ALIGN_FACTOR: Param = Param {
+    key: "align-factor",
+    legacy_key: "align-factor",
+    min: 0.0,
+    max: 10.0,
+    step: 0.1,
+    default: DEFAULT_ALIGN_FACTOR,
+    get: |boids| boids.align_factor,
+    set: |boids, val| boids.align_factor = val,
+};
+
+pub const COHESION_FACTOR: Param = Param {
+    key: "cohesion-factor",
+    legacy_key: "cohesion-factor",
+    min: 0.0,
+    max: 10.0,
+    step: 0.1,
+    default: DEFAULT_COHESION_FACTOR,
+    get: |boids| boids.cohesion_factor,
+    set: |boids, val| boids.cohesion_factor = val,
+};
+
+pub const SEPERATION_FACTOR: Param = Param {
+    key: "separation-factor",
+    legacy_key: "seperation-factor",
+    min: 0.0,
+    max: 10.0,
+    step: 0.1,
+    default: DEFAULT_SEPERATION_FACTOR,
+    get: |boids| boids.seperation_factor,
+    set: |boids, val| boids.seperation_factor = val,
+};
+
+pub const ANGST_FACTOR: Param = Param {
+    key: "angst-factor",
+    legacy_key: "angst-factor",
+    min: 0.0,
+    max: 20000.0,
+    step: 1.0,
+    default: DEFAULT_ANGST_FACTOR,
+    get: |boids| boids.angst_factor,
+    set: |boids, val| boids.angst_factor = val,
+};
+
+/// All parameters. The order is part of the compact url encoding,
+/// only ever append to it!
+pub const ALL: [&Param; 11] = [
+    &NR_OF_BOIDS,
+    &ALIGN_RADIUS,
+    &COHESION_RADIUS,
+    &SEPERATION_RADIUS,
+    &ANGST_RADIUS,
+    &MAX_SPEED,
+    &MAX_STEER,
+    &ALIGN_FACTOR,
+    &COHESION_FACTOR,
+    &SEPERATION_FACTOR,
+    &ANGST_FACTOR,
+];
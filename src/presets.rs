@@ -0,0 +1,197 @@
+use na::Vector2;
+use nalgebra as na;
+
+use std::f64;
+
+use crate::{
+    boids::Boids, params, theme::ThemeKind, utils as util, DEFAULT_ALIGN_FACTOR,
+    DEFAULT_ALIGN_RADIUS, DEFAULT_COHESION_FACTOR, DEFAULT_COHESION_RADIUS, DEFAULT_MAX_SPEED,
+    DEFAULT_MAX_STEER, DEFAULT_SEPERATION_FACTOR, DEFAULT_SEPERATION_RADIUS,
+};
+
+named_enum! {
+    /// Named parameter sets reproducing characteristic flocking regimes.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Preset {
+        Swarm => ("swarm", "Swarm"),
+        Torus => ("torus", "Torus / Milling"),
+        DynamicParallel => ("dynamic-parallel", "Dynamic Parallel Group"),
+        HighlyParallel => ("highly-parallel", "Highly Parallel Group"),
+        Gas => ("gas", "Dispersed Gas"),
+        PredatorHeavy => ("predator-heavy", "Predator Heavy"),
+    }
+}
+
+/// A complete configuration of the tunable [`Boids`] parameters.
+struct Values {
+    nr_of_boids: usize,
+    align_radius: f64,
+    cohesion_radius: f64,
+    seperation_radius: f64,
+    angst_radius: f64,
+    max_speed: f64,
+    max_steer: f64,
+    align_factor: f64,
+    cohesion_factor: f64,
+    seperation_factor: f64,
+    angst_factor: f64,
+    /// Number of stationary predators placed around the center
+    extra_predators: usize,
+    /// Theme switched to, the current one is kept if `None`
+    theme: Option<ThemeKind>,
+}
+
+impl Preset {
+    /// Overwrite every parameter of `boids` with the values of this preset.
+    /// All predators except the mouse follower are replaced. The theme is
+    /// left to the caller, see [`Preset::theme`].
+    pub fn apply(&self, boids: &mut Boids) {
+        let values = self.values();
+        util::change_number_of_boids(boids, values.nr_of_boids);
+        boids.align_radius_squared = values.align_radius.powf(2.0);
+        boids.cohesion_radius_squared = values.cohesion_radius.powf(2.0);
+        boids.seperation_radius_squared = values.seperation_radius.powf(2.0);
+        boids.angst_radius_squared = values.angst_radius.powf(2.0);
+        boids.max_speed = values.max_speed;
+        boids.max_steer = values.max_steer;
+        boids.align_factor = values.align_factor;
+        boids.cohesion_factor = values.cohesion_factor;
+        boids.seperation_factor = values.seperation_factor;
+        boids.angst_factor = values.angst_factor;
+        boids.predators.truncate(1);
+        let center = Vector2::new(boids.size.0 / 2.0, boids.size.1 / 2.0);
+        let distance = boids.size.0.min(boids.size.1) / 4.0;
+        for nr in 0..values.extra_predators {
+            let phi = nr as f64 * 2.0 * f64::consts::PI / values.extra_predators as f64;
+            boids
+                .predators
+                .push(center + Vector2::new(phi.cos(), phi.sin()) * distance);
+        }
+    }
+
+    /// Value this preset sets `param` to, its default if the preset leaves it alone.
+    pub fn value(&self, param: &params::Param) -> f64 {
+        let values = self.values();
+        match param.key {
+            key if key == params::NR_OF_BOIDS.key => values.nr_of_boids as f64,
+            key if key == params::ALIGN_RADIUS.key => values.align_radius,
+            key if key == params::COHESION_RADIUS.key => values.cohesion_radius,
+            key if key == params::SEPERATION_RADIUS.key => values.seperation_radius,
+            key if key == params::ANGST_RADIUS.key => values.angst_radius,
+            key if key == params::MAX_SPEED.key => values.max_speed,
+            key if key == params::MAX_STEER.key => values.max_steer,
+            key if key == params::ALIGN_FACTOR.key => values.align_factor,
+            key if key == params::COHESION_FACTOR.key => values.cohesion_factor,
+            key if key == params::SEPERATION_FACTOR.key => values.seperation_factor,
+            key if key == params::ANGST_FACTOR.key => values.angst_factor,
+            _ => param.default,
+        }
+    }
+
+    /// Theme to switch to along with the parameters, if any.
+    pub fn theme(&self) -> Option<ThemeKind> {
+        self.values().theme
+    }
+
+    fn values(&self) -> Values {
+        match self {
+            // Strong attraction, no alignment: an unordered, cohesive cloud
+            Preset::Swarm => Values {
+                nr_of_boids: 300,
+                align_radius: 10.0,
+                cohesion_radius: 150.0,
+                seperation_radius: 15.0,
+                angst_radius: 100.0,
+                max_speed: 150.0,
+                max_steer: 20.0,
+                align_factor: 0.0,
+                cohesion_factor: 0.1,
+                seperation_factor: 1.0,
+                angst_factor: 2000.0,
+                extra_predators: 0,
+                theme: None,
+            },
+            // Weak alignment and wide attraction: boids circle an empty core
+            Preset::Torus => Values {
+                nr_of_boids: 300,
+                align_radius: 25.0,
+                cohesion_radius: 200.0,
+                seperation_radius: 15.0,
+                angst_radius: 100.0,
+                max_speed: 250.0,
+                max_steer: 10.0,
+                align_factor: 0.1,
+                cohesion_factor: 0.2,
+                seperation_factor: 1.0,
+                angst_factor: 2000.0,
+                extra_predators: 0,
+                theme: None,
+            },
+            // Medium alignment: polarized groups that keep changing shape
+            Preset::DynamicParallel => Values {
+                nr_of_boids: 300,
+                align_radius: 60.0,
+                cohesion_radius: 100.0,
+                seperation_radius: 15.0,
+                angst_radius: 100.0,
+                max_speed: 300.0,
+                max_steer: 30.0,
+                align_factor: 0.3,
+                cohesion_factor: 0.02,
+                seperation_factor: 1.0,
+                angst_factor: 2000.0,
+                extra_predators: 0,
+                theme: None,
+            },
+            // Wide and strong alignment: rigid, highly polarized groups
+            Preset::HighlyParallel => Values {
+                nr_of_boids: 300,
+                align_radius: 150.0,
+                cohesion_radius: 150.0,
+                seperation_radius: 15.0,
+                angst_radius: 100.0,
+                max_speed: 300.0,
+                max_steer: 30.0,
+                align_factor: 1.0,
+                cohesion_factor: 0.01,
+                seperation_factor: 1.0,
+                angst_factor: 2000.0,
+                extra_predators: 0,
+                theme: None,
+            },
+            // Only repulsion: boids spread out evenly and move independently
+            Preset::Gas => Values {
+                nr_of_boids: 300,
+                align_radius: 0.0,
+                cohesion_radius: 0.0,
+                seperation_radius: 40.0,
+                angst_radius: 100.0,
+                max_speed: 200.0,
+                max_steer: 30.0,
+                align_factor: 0.0,
+                cohesion_factor: 0.0,
+                seperation_factor: 2.0,
+                angst_factor: 2000.0,
+                extra_predators: 0,
+                theme: None,
+            },
+            // Default flocking, but fleeing from several large predators
+            Preset::PredatorHeavy => Values {
+                nr_of_boids: 300,
+                align_radius: DEFAULT_ALIGN_RADIUS,
+                cohesion_radius: DEFAULT_COHESION_RADIUS,
+                seperation_radius: DEFAULT_SEPERATION_RADIUS,
+                angst_radius: 250.0,
+                max_speed: DEFAULT_MAX_SPEED,
+                max_steer: DEFAULT_MAX_STEER,
+                align_factor: DEFAULT_ALIGN_FACTOR,
+                cohesion_factor: DEFAULT_COHESION_FACTOR,
+                seperation_factor: DEFAULT_SEPERATION_FACTOR,
+                angst_factor: 20000.0,
+                extra_predators: 3,
+                // Red predators stand out on a dark background
+                theme: Some(ThemeKind::Dark),
+            },
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+
+use crate::{FRAME_TIME_HISTORY_LENGTH, PROFILE_SAMPLE_EVERY, PROFILE_SMOOTHING};
+
+/// Parts of a frame that are timed separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Neighbours,
+    Align,
+    Cohesion,
+    Seperation,
+    Angst,
+    Return,
+    Integration,
+    Flocks,
+    Background,
+    Boids,
+    Debug,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 11] = [
+        Phase::Neighbours,
+        Phase::Align,
+        Phase::Cohesion,
+        Phase::Seperation,
+        Phase::Angst,
+        Phase::Return,
+        Phase::Integration,
+        Phase::Flocks,
+        Phase::Background,
+        Phase::Boids,
+        Phase::Debug,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Phase::Neighbours => "Neighbour Search",
+            Phase::Align => "Align",
+            Phase::Cohesion => "Cohesion",
+            Phase::Seperation => "Seperation",
+            Phase::Angst => "Angst",
+            Phase::Return => "Return",
+            Phase::Integration => "Integration",
+            Phase::Flocks => "Flocks",
+            Phase::Background => "Background",
+            Phase::Boids => "Boids",
+            Phase::Debug => "Debug",
+        }
+    }
+
+    /// Whether this phase belongs to the simulation, not the rendering
+    pub fn is_simulation(&self) -> bool {
+        !matches!(self, Phase::Background | Phase::Boids | Phase::Debug)
+    }
+}
+
+/// Collects the time spent in every [`Phase`] and smoothes it over frames.
+///
+/// Simulation phases are timed for a fixed subset of the boids, see
+/// [`Sampler`]. With the coarse clock of browsers they are only meaningful
+/// as averages over many frames. Nothing is measured unless enabled.
+#[derive(Default)]
+pub struct Profiler {
+    pub enabled: bool,
+    /// Milliseconds spent per phase in the current frame
+    current: [f64; Phase::ALL.len()],
+    /// Rolling average of milliseconds spent per phase
+    averages: [f64; Phase::ALL.len()],
+    /// Duration of the most recent frames in milliseconds, oldest first
+    frame_times: VecDeque<f64>,
+}
+
+impl Profiler {
+    /// Run `f` and add the time it took to `phase`.
+    pub fn measure<T>(&mut self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        if self.enabled {
+            let start = now();
+            let result = f();
+            self.record(phase, now() - start);
+            result
+        } else {
+            f()
+        }
+    }
+
+    /// Add `millis` to the time spent in `phase` during the current frame.
+    pub fn record(&mut self, phase: Phase, millis: f64) {
+        if self.enabled {
+            self.current[phase as usize] += millis;
+        }
+    }
+
+    /// Fold the current frame into the averages and start a new one.
+    pub fn finish_frame(&mut self, frame_time: f64) {
+        if !self.enabled {
+            return;
+        }
+        for (average, current) in self.averages.iter_mut().zip(self.current.iter_mut()) {
+            *average += PROFILE_SMOOTHING * (*current - *average);
+            *current = 0.0;
+        }
+        if self.frame_times.len() == FRAME_TIME_HISTORY_LENGTH {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+
+    /// Rolling average of the milliseconds spent in `phase`.
+    pub fn average(&self, phase: Phase) -> f64 {
+        self.averages[phase as usize]
+    }
+
+    pub fn frame_times(&self) -> impl Iterator<Item = f64> + '_ {
+        self.frame_times.iter().copied()
+    }
+}
+
+/// Times the phases of every [`PROFILE_SAMPLE_EVERY`]th item of a loop and
+/// extrapolates them to all items, to keep the clock reads per item low.
+pub struct Sampler<'a> {
+    profiler: &'a mut Profiler,
+    /// Number of items every measured item stands for
+    weight: f64,
+    /// Whether the current item is measured
+    active: bool,
+}
+
+impl<'a> Sampler<'a> {
+    /// Sample a loop over `count` items.
+    pub fn new(profiler: &'a mut Profiler, count: usize) -> Self {
+        let samples = count.div_ceil(PROFILE_SAMPLE_EVERY);
+        Sampler {
+            profiler,
+            weight: count as f64 / samples.max(1) as f64,
+            active: false,
+        }
+    }
+
+    /// Start the item at `idx`, following measurements belong to it.
+    pub fn select(&mut self, idx: usize) {
+        self.active = self.profiler.enabled && idx.is_multiple_of(PROFILE_SAMPLE_EVERY);
+    }
+
+    /// Run `f` and, if the current item is sampled, add the
+    /// extrapolated time it took to `phase`.
+    pub fn measure<T>(&mut self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        if self.active {
+            let start = now();
+            let result = f();
+            self.profiler.record(phase, (now() - start) * self.weight);
+            result
+        } else {
+            f()
+        }
+    }
+}
+
+/// Current time in milliseconds, with an arbitrary origin.
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> f64 {
+    crate::utils::performance().now()
+}
+
+/// Current time in milliseconds, with an arbitrary origin.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> f64 {
+    use lazy_static::lazy_static;
+    use std::time::Instant;
+    lazy_static! {
+        static ref START: Instant = Instant::now();
+    }
+    START.elapsed().as_secs_f64() * 1000.0
+}
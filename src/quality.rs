@@ -0,0 +1,112 @@
+use std::f64;
+
+use crate::{boids::Boids, QUALITY_ADJUST_INTERVAL, QUALITY_MIN_BOIDS, QUALITY_TOLERANCE};
+
+/// Trades quality for speed to keep the frame rate near a target.
+///
+/// Quality is reduced in steps: first boids are drawn with less detail,
+/// then boids are suspended. Quality is restored in reverse order once
+/// there is headroom.
+pub struct QualityController {
+    /// Frame rate to aim for, `None` disables the controller
+    pub target_fps: Option<f64>,
+    /// Whether boids should be drawn with less detail
+    pub low_detail: bool,
+    /// Rolling average of the milliseconds spent simulating and rendering a frame
+    smoothed_work_time: f64,
+    /// Shortest time between two frames seen, the refresh interval of the display
+    shortest_interval: f64,
+    /// Milliseconds since the last adjustment
+    since_adjustment: f64,
+}
+
+impl QualityController {
+    pub fn new() -> Self {
+        QualityController {
+            target_fps: None,
+            low_detail: false,
+            smoothed_work_time: 0.0,
+            shortest_interval: f64::INFINITY,
+            since_adjustment: 0.0,
+        }
+    }
+
+    /// Change the target, restoring full quality if disabled.
+    pub fn set_target_fps(&mut self, boids: &mut Boids, target_fps: Option<f64>) {
+        self.target_fps = target_fps;
+        if target_fps.is_none() {
+            while self.restore(boids) {}
+        }
+    }
+
+    /// Feed the time spent simulating and rendering the last frame and the
+    /// time since the previous frame, adjusting the quality if necessary.
+    ///
+    /// The work is compared against the time available per frame at the
+    /// target frame rate, which is limited to the refresh rate of the display.
+    pub fn update(&mut self, boids: &mut Boids, work_time: f64, frame_interval: f64) {
+        let target_fps = match self.target_fps {
+            Some(target_fps) => target_fps,
+            None => return,
+        };
+        self.smoothed_work_time += 0.1 * (work_time - self.smoothed_work_time);
+        self.shortest_interval = self.shortest_interval.min(frame_interval);
+        self.since_adjustment += frame_interval;
+        if self.since_adjustment < QUALITY_ADJUST_INTERVAL {
+            return;
+        }
+        let refresh_rate = 1000.0 / self.shortest_interval;
+        let budget = 1000.0 / target_fps.min(refresh_rate);
+        if self.smoothed_work_time > budget * (1.0 + QUALITY_TOLERANCE) {
+            self.degrade(boids);
+            self.since_adjustment = 0.0;
+        } else if self.smoothed_work_time < budget * (1.0 - QUALITY_TOLERANCE) {
+            self.restore(boids);
+            self.since_adjustment = 0.0;
+        }
+    }
+
+    /// Lower the quality by one step, if possible.
+    fn degrade(&mut self, boids: &mut Boids) {
+        if !self.low_detail {
+            self.low_detail = true;
+        } else if boids.boids.len() > QUALITY_MIN_BOIDS {
+            // Suspend a tenth of the boids
+            let keep = (boids.boids.len() * 9 / 10).max(QUALITY_MIN_BOIDS);
+            let suspended = boids.boids.split_off(keep);
+            boids.suspended.extend(suspended);
+        }
+    }
+
+    /// Raise the quality by one step. Returns false if already at full quality.
+    fn restore(&mut self, boids: &mut Boids) -> bool {
+        if !boids.suspended.is_empty() {
+            // Resume the boids suspended last first
+            let resume = (boids.boids.len() / 10).max(1).min(boids.suspended.len());
+            let start = boids.suspended.len() - resume;
+            let resumed = boids.suspended.drain(start..);
+            boids.boids.extend(resumed);
+        } else if self.low_detail {
+            self.low_detail = false;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Human readable summary of the reductions currently in effect.
+    pub fn describe(&self, boids: &Boids) -> String {
+        let target_fps = match self.target_fps {
+            Some(target_fps) => target_fps,
+            None => return String::from("Adaptive quality off"),
+        };
+        let mut text = format!("Target {:.0}fps", target_fps);
+        if self.low_detail {
+            text += ", low detail";
+        }
+        if !boids.suspended.is_empty() {
+            text += &format!(", {} boids suspended", boids.suspended.len());
+        }
+        text
+    }
+}
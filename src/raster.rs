@@ -0,0 +1,363 @@
+use tiny_skia::{
+    Color, FillRule, Paint, PathBuilder, Pixmap, PixmapPaint, Rect, Stroke, Transform,
+};
+
+use std::{cell::RefCell, f64};
+
+use crate::draw::{parse_color, Draw};
+
+/// Number of line segments used for a full circle
+const ARC_SEGMENTS: f64 = 64.0;
+
+/// Height of a glyph of the bitmap font in pixels
+const GLYPH_HEIGHT: usize = 7;
+/// Distance between the left edges of two characters in pixels
+const GLYPH_ADVANCE: usize = 6;
+
+/// Software rasteriser drawing into a pixmap, used to render without a browser.
+///
+/// Text is drawn with a built-in 5x7 bitmap font, close in size to the
+/// default 10px font of the canvas. Only printable ASCII has glyphs, other
+/// characters are drawn as a box.
+pub struct Raster {
+    state: RefCell<State>,
+}
+
+struct State {
+    pixmap: Pixmap,
+    transform: Transform,
+    fill: Color,
+    stroke: Color,
+    line_width: f32,
+    alpha: f32,
+    path: PathBuilder,
+}
+
+impl Raster {
+    /// Create a transparent raster of the given size in pixels.
+    pub fn new(width: u32, height: u32) -> Option<Self> {
+        Some(Raster {
+            state: RefCell::new(State {
+                pixmap: Pixmap::new(width, height)?,
+                transform: Transform::identity(),
+                fill: Color::BLACK,
+                stroke: Color::BLACK,
+                line_width: 1.0,
+                alpha: 1.0,
+                path: PathBuilder::new(),
+            }),
+        })
+    }
+
+    pub fn into_pixmap(self) -> Pixmap {
+        self.state.into_inner().pixmap
+    }
+}
+
+fn to_color(color: &str) -> Color {
+    let [r, g, b, a] = parse_color(color);
+    Color::from_rgba8(r, g, b, a)
+}
+
+fn paint(mut color: Color, alpha: f32) -> Paint<'static> {
+    color.apply_opacity(alpha);
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.anti_alias = true;
+    paint
+}
+
+impl Draw for Raster {
+    type Image = Pixmap;
+
+    fn set_transform(&self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
+        self.state.borrow_mut().transform =
+            Transform::from_row(a as f32, b as f32, c as f32, d as f32, e as f32, f as f32);
+    }
+
+    fn set_fill_style(&self, color: &str) {
+        self.state.borrow_mut().fill = to_color(color);
+    }
+
+    fn set_stroke_style(&self, color: &str) {
+        self.state.borrow_mut().stroke = to_color(color);
+    }
+
+    fn set_line_width(&self, width: f64) {
+        self.state.borrow_mut().line_width = width as f32;
+    }
+
+    fn set_global_alpha(&self, alpha: f64) {
+        self.state.borrow_mut().alpha = alpha as f32;
+    }
+
+    fn begin_path(&self) {
+        self.state.borrow_mut().path.clear();
+    }
+
+    fn move_to(&self, x: f64, y: f64) {
+        self.state.borrow_mut().path.move_to(x as f32, y as f32);
+    }
+
+    fn line_to(&self, x: f64, y: f64) {
+        self.state.borrow_mut().path.line_to(x as f32, y as f32);
+    }
+
+    fn arc(&self, x: f64, y: f64, radius: f64, start: f64, end: f64) {
+        // Approximate the arc with line segments
+        let segments = ((end - start).abs() / (2.0 * f64::consts::PI) * ARC_SEGMENTS)
+            .ceil()
+            .max(1.0);
+        let path = &mut self.state.borrow_mut().path;
+        for idx in 0..=segments as usize {
+            let angle = start + (end - start) * idx as f64 / segments;
+            let (px, py) = (x + radius * angle.cos(), y + radius * angle.sin());
+            if path.is_empty() {
+                path.move_to(px as f32, py as f32);
+            } else {
+                path.line_to(px as f32, py as f32);
+            }
+        }
+    }
+
+    fn rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        if let Some(rect) = Rect::from_xywh(x as f32, y as f32, width as f32, height as f32) {
+            self.state.borrow_mut().path.push_rect(rect);
+        }
+    }
+
+    fn fill(&self) {
+        let state = &mut *self.state.borrow_mut();
+        if let Some(path) = state.path.clone().finish() {
+            state.pixmap.fill_path(
+                &path,
+                &paint(state.fill, state.alpha),
+                FillRule::Winding,
+                state.transform,
+                None,
+            );
+        }
+    }
+
+    fn stroke(&self) {
+        let state = &mut *self.state.borrow_mut();
+        if let Some(path) = state.path.clone().finish() {
+            let stroke = Stroke {
+                width: state.line_width,
+                ..Stroke::default()
+            };
+            state.pixmap.stroke_path(
+                &path,
+                &paint(state.stroke, state.alpha),
+                &stroke,
+                state.transform,
+                None,
+            );
+        }
+    }
+
+    fn fill_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        let state = &mut *self.state.borrow_mut();
+        if let Some(rect) = Rect::from_xywh(x as f32, y as f32, width as f32, height as f32) {
+            state
+                .pixmap
+                .fill_rect(rect, &paint(state.fill, state.alpha), state.transform, None);
+        }
+    }
+
+    fn fill_text(&self, text: &str, x: f64, y: f64) {
+        let state = &mut *self.state.borrow_mut();
+        let mut path = PathBuilder::new();
+        // The baseline is at the bottom of the glyphs, as with the canvas
+        let top = y as f32 - GLYPH_HEIGHT as f32;
+        for (idx, character) in text.chars().enumerate() {
+            let left = x as f32 + (idx * GLYPH_ADVANCE) as f32;
+            for (column, bits) in glyph(character).iter().enumerate() {
+                for row in (0..GLYPH_HEIGHT).filter(|row| bits & 1 << row != 0) {
+                    if let Some(rect) =
+                        Rect::from_xywh(left + column as f32, top + row as f32, 1.0, 1.0)
+                    {
+                        path.push_rect(rect);
+                    }
+                }
+            }
+        }
+        if let Some(path) = path.finish() {
+            let mut paint = paint(state.fill, state.alpha);
+            // Keep the pixels of the glyphs sharp
+            paint.anti_alias = false;
+            state
+                .pixmap
+                .fill_path(&path, &paint, FillRule::Winding, state.transform, None);
+        }
+    }
+
+    fn draw_image(&self, image: &Pixmap, x: f64, y: f64, width: f64, height: f64) {
+        let state = &mut *self.state.borrow_mut();
+        let scale = Transform::from_row(
+            (width / image.width() as f64) as f32,
+            0.0,
+            0.0,
+            (height / image.height() as f64) as f32,
+            x as f32,
+            y as f32,
+        );
+        state.pixmap.draw_pixmap(
+            0,
+            0,
+            image.as_ref(),
+            &PixmapPaint {
+                opacity: state.alpha,
+                ..PixmapPaint::default()
+            },
+            state.transform.pre_concat(scale),
+            None,
+        );
+    }
+}
+
+/// Columns of the printable ASCII characters, starting with the space.
+/// The lowest bit is the top row.
+const GLYPHS: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x5f, 0x00, 0x00],
+    [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7f, 0x14, 0x7f, 0x14],
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12],
+    [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x55, 0x22, 0x50],
+    [0x00, 0x05, 0x03, 0x00, 0x00],
+    [0x00, 0x1c, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1c, 0x00],
+    [0x14, 0x08, 0x3e, 0x08, 0x14],
+    [0x08, 0x08, 0x3e, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00],
+    [0x08, 0x08, 0x08, 0x08, 0x08],
+    [0x00, 0x60, 0x60, 0x00, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02],
+    [0x3e, 0x51, 0x49, 0x45, 0x3e],
+    [0x00, 0x42, 0x7f, 0x40, 0x00],
+    [0x42, 0x61, 0x51, 0x49, 0x46],
+    [0x21, 0x41, 0x45, 0x4b, 0x31],
+    [0x18, 0x14, 0x12, 0x7f, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39],
+    [0x3c, 0x4a, 0x49, 0x49, 0x30],
+    [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36],
+    [0x06, 0x49, 0x49, 0x29, 0x1e],
+    [0x00, 0x36, 0x36, 0x00, 0x00],
+    [0x00, 0x56, 0x36, 0x00, 0x00],
+    [0x08, 0x14, 0x22, 0x41, 0x00],
+    [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08],
+    [0x02, 0x01, 0x51, 0x09, 0x06],
+    [0x32, 0x49, 0x79, 0x41, 0x3e],
+    [0x7e, 0x11, 0x11, 0x11, 0x7e],
+    [0x7f, 0x49, 0x49, 0x49, 0x36],
+    [0x3e, 0x41, 0x41, 0x41, 0x22],
+    [0x7f, 0x41, 0x41, 0x22, 0x1c],
+    [0x7f, 0x49, 0x49, 0x49, 0x41],
+    [0x7f, 0x09, 0x09, 0x09, 0x01],
+    [0x3e, 0x41, 0x49, 0x49, 0x7a],
+    [0x7f, 0x08, 0x08, 0x08, 0x7f],
+    [0x00, 0x41, 0x7f, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3f, 0x01],
+    [0x7f, 0x08, 0x14, 0x22, 0x41],
+    [0x7f, 0x40, 0x40, 0x40, 0x40],
+    [0x7f, 0x02, 0x0c, 0x02, 0x7f],
+    [0x7f, 0x04, 0x08, 0x10, 0x7f],
+    [0x3e, 0x41, 0x41, 0x41, 0x3e],
+    [0x7f, 0x09, 0x09, 0x09, 0x06],
+    [0x3e, 0x41, 0x51, 0x21, 0x5e],
+    [0x7f, 0x09, 0x19, 0x29, 0x46],
+    [0x46, 0x49, 0x49, 0x49, 0x31],
+    [0x01, 0x01, 0x7f, 0x01, 0x01],
+    [0x3f, 0x40, 0x40, 0x40, 0x3f],
+    [0x1f, 0x20, 0x40, 0x20, 0x1f],
+    [0x3f, 0x40, 0x38, 0x40, 0x3f],
+    [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x07, 0x08, 0x70, 0x08, 0x07],
+    [0x61, 0x51, 0x49, 0x45, 0x43],
+    [0x00, 0x7f, 0x41, 0x41, 0x00],
+    [0x02, 0x04, 0x08, 0x10, 0x20],
+    [0x00, 0x41, 0x41, 0x7f, 0x00],
+    [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40],
+    [0x00, 0x01, 0x02, 0x04, 0x00],
+    [0x20, 0x54, 0x54, 0x54, 0x78],
+    [0x7f, 0x48, 0x44, 0x44, 0x38],
+    [0x38, 0x44, 0x44, 0x44, 0x20],
+    [0x38, 0x44, 0x44, 0x48, 0x7f],
+    [0x38, 0x54, 0x54, 0x54, 0x18],
+    [0x08, 0x7e, 0x09, 0x01, 0x02],
+    [0x0c, 0x52, 0x52, 0x52, 0x3e],
+    [0x7f, 0x08, 0x04, 0x04, 0x78],
+    [0x00, 0x44, 0x7d, 0x40, 0x00],
+    [0x20, 0x40, 0x44, 0x3d, 0x00],
+    [0x7f, 0x10, 0x28, 0x44, 0x00],
+    [0x00, 0x41, 0x7f, 0x40, 0x00],
+    [0x7c, 0x04, 0x18, 0x04, 0x78],
+    [0x7c, 0x08, 0x04, 0x04, 0x78],
+    [0x38, 0x44, 0x44, 0x44, 0x38],
+    [0x7c, 0x14, 0x14, 0x14, 0x08],
+    [0x08, 0x14, 0x14, 0x18, 0x7c],
+    [0x7c, 0x08, 0x04, 0x04, 0x08],
+    [0x48, 0x54, 0x54, 0x54, 0x20],
+    [0x04, 0x3f, 0x44, 0x40, 0x20],
+    [0x3c, 0x40, 0x40, 0x20, 0x7c],
+    [0x1c, 0x20, 0x40, 0x20, 0x1c],
+    [0x3c, 0x40, 0x30, 0x40, 0x3c],
+    [0x44, 0x28, 0x10, 0x28, 0x44],
+    [0x0c, 0x50, 0x50, 0x50, 0x3c],
+    [0x44, 0x64, 0x54, 0x4c, 0x44],
+    [0x00, 0x08, 0x36, 0x41, 0x00],
+    [0x00, 0x00, 0x7f, 0x00, 0x00],
+    [0x00, 0x41, 0x36, 0x08, 0x00],
+    [0x08, 0x04, 0x08, 0x10, 0x08],
+];
+
+/// Columns of the glyph for a character, a box if there is none.
+fn glyph(character: char) -> [u8; 5] {
+    match character {
+        ' '..='~' => GLYPHS[character as usize - ' ' as usize],
+        '°' => [0x00, 0x06, 0x09, 0x09, 0x06],
+        _ => [0x7f, 0x41, 0x41, 0x41, 0x7f],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Number of opaque pixels in the raster.
+    fn covered(raster: Raster) -> usize {
+        let pixmap = raster.into_pixmap();
+        pixmap
+            .pixels()
+            .iter()
+            .filter(|pixel| pixel.alpha() > 0)
+            .count()
+    }
+
+    #[test]
+    fn text_is_drawn_above_the_baseline() {
+        let raster = Raster::new(20, 20).unwrap();
+        raster.set_fill_style("red");
+        raster.fill_text("I", 0.0, 7.0);
+        let pixmap = raster.into_pixmap();
+        // The vertical bar of the I in the middle column, top to bottom
+        for row in 0..7 {
+            assert_eq!(pixmap.pixel(2, row).unwrap().red(), 255, "row {}", row);
+        }
+        assert_eq!(pixmap.pixel(2, 7).unwrap().alpha(), 0);
+    }
+
+    #[test]
+    fn every_character_is_visible() {
+        for character in (b'!'..=b'~').map(char::from).chain("°é".chars()) {
+            let raster = Raster::new(10, 10).unwrap();
+            raster.fill_text(&character.to_string(), 0.0, 8.0);
+            assert!(covered(raster) > 0, "{:?}", character);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use gif::{Encoder, EncodingError, Frame, Repeat};
+
+use std::collections::VecDeque;
+
+/// Quantization speed passed to the gif encoder, from 1 (best) to 30 (fastest)
+const QUANTIZATION_SPEED: i32 = 10;
+
+/// Encodes captured frames into a looping animated GIF.
+///
+/// Encoding is slow, so frames are only queued when they arrive and
+/// encoded later with [`Recording::encode_next`], one at a time.
+pub struct Recording {
+    pub width: u16,
+    pub height: u16,
+    /// Frames per second of the recording
+    pub fps: f64,
+    encoder: Encoder<Vec<u8>>,
+    /// Captured rgba frames that are not encoded yet
+    pending: VecDeque<Vec<u8>>,
+    frames: usize,
+    /// Milliseconds since the last captured frame
+    since_capture: f64,
+}
+
+impl Recording {
+    pub fn new(width: u16, height: u16, fps: f64) -> Result<Self, EncodingError> {
+        let mut encoder = Encoder::new(Vec::new(), width, height, &[])?;
+        encoder.set_repeat(Repeat::Infinite)?;
+        Ok(Recording {
+            width,
+            height,
+            fps,
+            encoder,
+            pending: VecDeque::new(),
+            frames: 0,
+            since_capture: 0.0,
+        })
+    }
+
+    /// Feed the simulated milliseconds since the last call. Returns whether
+    /// it is time to capture the next frame to keep the frame rate.
+    pub fn frame_due(&mut self, time_passed: f64) -> bool {
+        let interval = 1000.0 / self.fps;
+        self.since_capture += time_passed;
+        if self.frames == 0 || self.since_capture >= interval {
+            // Don't try to catch up after a slow frame
+            self.since_capture = (self.since_capture - interval).clamp(0.0, interval);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Queue a frame of `width * height` rgba pixels.
+    pub fn push(&mut self, rgba: Vec<u8>) {
+        self.pending.push_back(rgba);
+        self.frames += 1;
+    }
+
+    /// Number of queued frames that are not encoded yet.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Encode the oldest queued frame, if any, and free its pixels.
+    pub fn encode_next(&mut self) -> Result<(), EncodingError> {
+        if let Some(mut rgba) = self.pending.pop_front() {
+            let mut frame =
+                Frame::from_rgba_speed(self.width, self.height, &mut rgba, QUANTIZATION_SPEED);
+            // The delay is given in hundredths of a second
+            frame.delay = (100.0 / self.fps).round() as u16;
+            self.encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    /// Encode all queued frames.
+    pub fn encode_pending(&mut self) -> Result<(), EncodingError> {
+        while !self.pending.is_empty() {
+            self.encode_next()?;
+        }
+        Ok(())
+    }
+
+    /// Number of frames recorded so far.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// The encoded GIF, including the frames not encoded yet.
+    pub fn finish(mut self) -> Result<Vec<u8>, EncodingError> {
+        self.encode_pending()?;
+        Ok(self.encoder.into_inner()?)
+    }
+}
@@ -0,0 +1,223 @@
+use log::warn;
+use na::Vector2;
+use nalgebra as na;
+use yew::web_sys::{CanvasRenderingContext2d as Ctx, HtmlImageElement};
+
+use std::f64;
+
+use crate::{
+    boids::Boids, camera::Camera, coloring::Coloring, detail::Detail, draw::Draw, model::Model,
+    shape::Appearance, theme::Theme, trails::TrailMode, webgl::WebGlRenderer,
+    PREDATOR_CLICK_RADIUS_SQUARED, SPRITE_SIZE,
+};
+
+/// Side length of boids drawn as dots, at scale one
+const DOT_SIZE: f64 = 3.0;
+
+/// A way of drawing the world and the boids in it.
+///
+/// Both steps draw in screen coordinates. The debug overlay is drawn
+/// onto `ctx` afterwards, on top of whatever the renderer produced.
+pub trait Renderer {
+    /// Draw everything outside and the background of the world.
+    fn draw_background(&mut self, model: &Model, ctx: &Ctx);
+    /// Draw all boids, as `sprite` if one is given.
+    fn draw_boids(&mut self, model: &Model, ctx: &Ctx, sprite: Option<&HtmlImageElement>);
+}
+
+named_enum! {
+    /// The available renderers, selectable in the settings panel.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum RendererKind {
+        Canvas => ("canvas", "Canvas 2D"),
+        WebGl => ("webgl", "WebGL 2"),
+    }
+}
+
+impl RendererKind {
+    /// Create a renderer of this kind, falling back to the
+    /// Canvas 2D renderer if WebGL is not available.
+    pub fn create(&self) -> (RendererKind, Box<dyn Renderer>) {
+        match self {
+            RendererKind::Canvas => (RendererKind::Canvas, Box::new(CanvasRenderer)),
+            RendererKind::WebGl => match WebGlRenderer::new() {
+                Ok(renderer) => (RendererKind::WebGl, Box::new(renderer)),
+                Err(why) => {
+                    warn!("WebGL not available, using Canvas 2D: {}", why);
+                    (RendererKind::Canvas, Box::new(CanvasRenderer))
+                }
+            },
+        }
+    }
+}
+
+/// Draws every boid as part of one large 2D path per colour.
+pub struct CanvasRenderer;
+
+impl Renderer for CanvasRenderer {
+    fn draw_background(&mut self, model: &Model, ctx: &Ctx) {
+        // Let the previous frames shine through to fade them out gradually
+        let alpha = if model.trail_mode == TrailMode::Fade {
+            1.0 - model.trail_opacity
+        } else {
+            1.0
+        };
+        draw_background(
+            ctx,
+            &model.boids,
+            &model.camera,
+            &model.theme,
+            model.special_mode,
+            alpha,
+        );
+    }
+
+    fn draw_boids(&mut self, model: &Model, ctx: &Ctx, sprite: Option<&HtmlImageElement>) {
+        draw_boids(
+            ctx,
+            &model.boids,
+            &model.camera,
+            &model.coloring,
+            &model.active_appearance(),
+            model.detail(),
+            sprite,
+        );
+    }
+}
+
+/// Fill everything outside and the world with the colours of the
+/// theme, the world using the given opacity.
+pub fn draw_background(
+    ctx: &impl Draw,
+    boids: &Boids,
+    camera: &Camera,
+    theme: &Theme,
+    special_mode: bool,
+    alpha: f64,
+) {
+    let (width, height) = camera.view;
+    ctx.set_fill_style(&theme.outside);
+    if alpha < 1.0 {
+        // Painting over the world would clear what is meant to fade,
+        // only fill the strips around it
+        let min = camera.to_screen(Vector2::zeros());
+        let max = camera.to_screen(Vector2::new(boids.size.0, boids.size.1));
+        let (top, bottom) = (min.y.clamp(0.0, height), max.y.clamp(0.0, height));
+        let (left, right) = (min.x.clamp(0.0, width), max.x.clamp(0.0, width));
+        ctx.fill_rect(0.0, 0.0, width, top);
+        ctx.fill_rect(0.0, bottom, width, height - bottom);
+        ctx.fill_rect(0.0, top, left, bottom - top);
+        ctx.fill_rect(right, top, width - right, bottom - top);
+    } else {
+        ctx.fill_rect(0.0, 0.0, width, height);
+    }
+    camera.apply(ctx);
+    if special_mode {
+        ctx.set_fill_style(&theme.special_background);
+    } else {
+        ctx.set_fill_style(&theme.background);
+    }
+    ctx.set_global_alpha(alpha);
+    ctx.fill_rect(0.0, 0.0, boids.size.0, boids.size.1);
+    ctx.set_global_alpha(1.0);
+    camera.reset(ctx);
+}
+
+/// Draw all boids in the colours given by `coloring`, using one path
+/// per colour. If a `sprite` is given, it is drawn in place of every boid
+/// at full `detail`, see [`Detail::resolve`].
+pub fn draw_boids<D: Draw>(
+    ctx: &D,
+    boids: &Boids,
+    camera: &Camera,
+    coloring: &Coloring,
+    appearance: &Appearance,
+    detail: Detail,
+    sprite: Option<&D::Image>,
+) {
+    if let (Some(sprite), Detail::Full) = (sprite, detail) {
+        let size = SPRITE_SIZE * appearance.scale;
+        for boid in &boids.boids {
+            let angle = if appearance.rotate_sprite {
+                boid.sprite_angle()
+            } else {
+                0.0
+            };
+            camera.apply_at(ctx, boid.pos, angle);
+            ctx.draw_image(sprite, -size / 2.0, -size / 2.0, size, size);
+        }
+        camera.reset(ctx);
+        return;
+    }
+    let mut groups = vec![Vec::new(); coloring.palette.len()];
+    for (boid, color) in boids.boids.iter().zip(&coloring.colors) {
+        groups[*color].push(boid);
+    }
+    let size = match detail {
+        Detail::Dots => DOT_SIZE * appearance.scale,
+        // One device pixel in world units
+        _ => 1.0 / (camera.zoom * camera.pixel_ratio),
+    };
+    camera.apply(ctx);
+    for (group, color) in groups.iter().zip(&coloring.palette) {
+        if group.is_empty() {
+            continue;
+        }
+        ctx.begin_path();
+        ctx.set_fill_style(color);
+        for boid in group {
+            match detail {
+                Detail::Dots | Detail::Pixels => {
+                    ctx.rect(boid.pos.x - size / 2.0, boid.pos.y - size / 2.0, size, size)
+                }
+                Detail::Auto | Detail::Full => boid.render(ctx, appearance),
+            }
+        }
+        ctx.fill();
+    }
+    camera.reset(ctx);
+}
+
+/// Draw every predator as dot.
+pub fn draw_predators(ctx: &impl Draw, boids: &Boids, camera: &Camera, theme: &Theme) {
+    camera.apply(ctx);
+    ctx.begin_path();
+    ctx.set_fill_style(&theme.predator);
+    let radius = PREDATOR_CLICK_RADIUS_SQUARED.sqrt();
+    for predator in &boids.predators {
+        ctx.move_to(predator.x + radius, predator.y);
+        ctx.arc(predator.x, predator.y, radius, 0.0, 2.0 * f64::consts::PI);
+    }
+    ctx.fill();
+    camera.reset(ctx);
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::{draw::parse_color, raster::Raster};
+
+    #[test]
+    fn fading_background_keeps_the_world() {
+        let mut boids = Boids::new(100.0, 50.0);
+        boids.boids.clear();
+        let mut camera = Camera::new((200.0, 100.0));
+        camera.fit(boids.size);
+        camera.pan(Vector2::new(20.0, 10.0));
+        let theme = Theme::default();
+        let raster = Raster::new(200, 100).unwrap();
+        raster.set_fill_style("red");
+        raster.fill_rect(0.0, 0.0, 200.0, 100.0);
+        draw_background(&raster, &boids, &camera, &theme, false, 0.0);
+        let pixmap = raster.into_pixmap();
+        let rgba = |x, y| {
+            let pixel = pixmap.pixel(x, y).unwrap();
+            [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+        };
+        // The world covers x 20..220 and y 10..110 on screen
+        assert_eq!(rgba(100, 50), parse_color("red"));
+        for (x, y) in [(10, 50), (100, 5), (19, 99), (0, 0)] {
+            assert_eq!(rgba(x, y), parse_color(&theme.outside), "{} {}", x, y);
+        }
+    }
+}
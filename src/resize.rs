@@ -0,0 +1,114 @@
+use na::Vector2;
+use nalgebra as na;
+
+use crate::{boid::Boid, boids::Boids, camera::Camera};
+
+named_enum! {
+    /// What happens to the world when the window changes its size.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ResizePolicy {
+        /// Resize the world and move every boid proportionally
+        Rescale => ("rescale", "Rescale Positions"),
+        /// Resize the world and push boids outside of it back to the border
+        Clamp => ("clamp", "Clamp Positions"),
+        /// Keep the world as is and fit it into the window
+        Letterbox => ("letterbox", "Keep World Fixed"),
+    }
+}
+
+impl ResizePolicy {
+    /// Adapt world and camera to a new window size.
+    /// The world is `world_scale` times the size of the window.
+    ///
+    /// Empty windows, like minimized ones, are ignored. Scaling the
+    /// world down to nothing would lose the positions of all boids.
+    pub fn resize(
+        &self,
+        boids: &mut Boids,
+        camera: &mut Camera,
+        view: (f64, f64),
+        world_scale: f64,
+    ) {
+        let size = (view.0 * world_scale, view.1 * world_scale);
+        let empty = |(width, height): (f64, f64)| !(width > 0.0 && height > 0.0);
+        if empty(size) {
+            return;
+        }
+        camera.view = view;
+        match self {
+            // There are no positions to scale in an empty world, place the boids anew
+            ResizePolicy::Rescale if empty(boids.size) => {
+                boids.size = size;
+                boids.scatter();
+            }
+            ResizePolicy::Rescale => {
+                let scale = Vector2::new(size.0 / boids.size.0, size.1 / boids.size.1);
+                move_all(boids, |pos| pos.component_mul(&scale));
+                boids.size = size;
+            }
+            ResizePolicy::Clamp => {
+                move_all(boids, |pos| {
+                    Vector2::new(pos.x.clamp(0.0, size.0), pos.y.clamp(0.0, size.1))
+                });
+                boids.size = size;
+            }
+            ResizePolicy::Letterbox => {}
+        }
+        camera.fit(boids.size);
+    }
+}
+
+/// Move every boid, suspended or not, and every stationary predator,
+/// keeping the bucket ids in sync.
+fn move_all(boids: &mut Boids, f: impl Fn(Vector2<f64>) -> Vector2<f64>) {
+    for boid in boids.boids.iter_mut().chain(&mut boids.suspended) {
+        boid.pos = f(boid.pos);
+        boid.id = Boid::bucket_of(&boid.pos);
+        boid.trail.clear();
+    }
+    // The first predator follows the mouse and is moved by it
+    for predator in boids.predators.iter_mut().skip(1) {
+        *predator = f(*predator);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rescale_moves_boids_proportionally() {
+        let mut boids = Boids::new(800.0, 600.0);
+        let mut camera = Camera::new((800.0, 600.0));
+        boids.boids[0].pos = Vector2::new(400.0, 150.0);
+        ResizePolicy::Rescale.resize(&mut boids, &mut camera, (400.0, 1200.0), 1.0);
+        assert_eq!(boids.size, (400.0, 1200.0));
+        assert_eq!(boids.boids[0].pos, Vector2::new(200.0, 300.0));
+    }
+
+    #[test]
+    fn clamp_moves_suspended_boids() {
+        let mut boids = Boids::new(800.0, 600.0);
+        let mut camera = Camera::new((800.0, 600.0));
+        boids.boids[0].pos = Vector2::new(700.0, 500.0);
+        boids.suspended = boids.boids.split_off(1);
+        boids.suspended[0].pos = Vector2::new(750.0, 50.0);
+        ResizePolicy::Clamp.resize(&mut boids, &mut camera, (400.0, 300.0), 1.0);
+        assert_eq!(boids.boids[0].pos, Vector2::new(400.0, 300.0));
+        assert_eq!(boids.suspended[0].pos, Vector2::new(400.0, 50.0));
+    }
+
+    #[test]
+    fn empty_windows_are_ignored() {
+        let mut boids = Boids::new(800.0, 600.0);
+        let mut camera = Camera::new((800.0, 600.0));
+        boids.boids[0].pos = Vector2::new(400.0, 150.0);
+        for policy in ResizePolicy::ALL.iter() {
+            policy.resize(&mut boids, &mut camera, (0.0, 600.0), 1.0);
+            policy.resize(&mut boids, &mut camera, (800.0, 0.0), 1.0);
+        }
+        ResizePolicy::Rescale.resize(&mut boids, &mut camera, (1600.0, 600.0), 1.0);
+        assert_eq!(camera.view, (1600.0, 600.0));
+        assert_eq!(boids.boids[0].pos, Vector2::new(800.0, 150.0));
+    }
+}
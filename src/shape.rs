@@ -0,0 +1,75 @@
+named_enum! {
+    /// How boids are drawn.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Shape {
+        Triangle => ("triangle", "Triangle"),
+        Arrow => ("arrow", "Arrow"),
+        Dot => ("dot", "Dot"),
+        Fish => ("fish", "Fish"),
+        Bird => ("bird", "Bird"),
+        /// An image loaded from a url or file, see [`Appearance::rotate_sprite`]
+        Sprite => ("sprite", "Image"),
+    }
+}
+
+impl Shape {
+    /// Closed outline of the shape at scale one, pointing along the y axis.
+    /// The dot has none, sprites fall back to the triangle.
+    pub fn outline(&self) -> &'static [(f64, f64)] {
+        match self {
+            Shape::Triangle | Shape::Sprite => &[(-3.0, -3.0), (0.0, 8.0), (3.0, -3.0)],
+            Shape::Arrow => &[(0.0, 8.0), (4.0, -3.0), (0.0, 0.0), (-4.0, -3.0)],
+            Shape::Dot => &[],
+            Shape::Fish => &[
+                (0.0, 8.0),
+                (2.0, 6.0),
+                (3.0, 3.0),
+                (2.5, 0.0),
+                (1.0, -2.0),
+                (3.0, -5.0),
+                (0.0, -3.5),
+                (-3.0, -5.0),
+                (-1.0, -2.0),
+                (-2.5, 0.0),
+                (-3.0, 3.0),
+                (-2.0, 6.0),
+            ],
+            Shape::Bird => &[
+                (0.0, 8.0),
+                (1.0, 6.0),
+                (1.0, 3.0),
+                (7.0, 1.0),
+                (8.0, -1.0),
+                (1.0, -0.5),
+                (2.5, -4.0),
+                (0.0, -3.0),
+                (-2.5, -4.0),
+                (-1.0, -0.5),
+                (-8.0, -1.0),
+                (-7.0, 1.0),
+                (-1.0, 3.0),
+                (-1.0, 6.0),
+            ],
+        }
+    }
+}
+
+/// Shape and size of the boids.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Appearance {
+    pub shape: Shape,
+    /// Size relative to the default size of shapes and [`SPRITE_SIZE`](crate::SPRITE_SIZE)
+    pub scale: f64,
+    /// Whether sprites are turned to the heading, they are expected to face up
+    pub rotate_sprite: bool,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Appearance {
+            shape: Shape::Triangle,
+            scale: 1.0,
+            rotate_sprite: true,
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::{cell::RefCell, f64, fmt::Write};
+
+use crate::draw::{parse_color, Draw};
+
+/// Records drawing operations as SVG document.
+///
+/// Images are referenced by their url.
+pub struct Svg {
+    width: f64,
+    height: f64,
+    state: RefCell<State>,
+}
+
+struct State {
+    transform: [f64; 6],
+    fill: String,
+    stroke: String,
+    line_width: f64,
+    alpha: f64,
+    /// Path data of the current path
+    path: String,
+    /// Elements drawn so far
+    body: String,
+}
+
+impl Svg {
+    pub fn new(width: f64, height: f64) -> Self {
+        Svg {
+            width,
+            height,
+            state: RefCell::new(State {
+                transform: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+                fill: String::from("black"),
+                stroke: String::from("black"),
+                line_width: 1.0,
+                alpha: 1.0,
+                path: String::new(),
+                body: String::new(),
+            }),
+        }
+    }
+
+    /// The complete SVG document.
+    pub fn finish(self) -> String {
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n{}</svg>\n",
+            self.state.into_inner().body,
+            w = self.width,
+            h = self.height,
+        )
+    }
+}
+
+impl State {
+    /// Attributes shared by all elements.
+    fn attrs(&self) -> String {
+        let [a, b, c, d, e, f] = self.transform;
+        let mut attrs = format!("transform=\"matrix({} {} {} {} {} {})\"", a, b, c, d, e, f);
+        if self.alpha < 1.0 {
+            write!(attrs, " opacity=\"{}\"", self.alpha).unwrap();
+        }
+        attrs
+    }
+
+    fn path_to(&mut self, command: char, x: f64, y: f64) {
+        write!(self.path, "{}{:.2} {:.2} ", command, x, y).unwrap();
+    }
+}
+
+/// The `attr` attribute painting with `color`. Colours with alpha are split
+/// into colour and opacity, SVG 1.1 does not know `#rrggbbaa`.
+fn paint(attr: &str, color: &str) -> String {
+    if color.starts_with('#') && color.len() == 9 {
+        let [r, g, b, a] = parse_color(color);
+        format!(
+            "{attr}=\"#{:02x}{:02x}{:02x}\" {attr}-opacity=\"{}\"",
+            r,
+            g,
+            b,
+            (a as f64 / 255.0 * 1000.0).round() / 1000.0,
+            attr = attr
+        )
+    } else {
+        format!("{}=\"{}\"", attr, escape(color))
+    }
+}
+
+/// Escape text for use in SVG content and attributes.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Draw for Svg {
+    type Image = String;
+
+    fn set_transform(&self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
+        self.state.borrow_mut().transform = [a, b, c, d, e, f];
+    }
+
+    fn set_fill_style(&self, color: &str) {
+        self.state.borrow_mut().fill = String::from(color);
+    }
+
+    fn set_stroke_style(&self, color: &str) {
+        self.state.borrow_mut().stroke = String::from(color);
+    }
+
+    fn set_line_width(&self, width: f64) {
+        self.state.borrow_mut().line_width = width;
+    }
+
+    fn set_global_alpha(&self, alpha: f64) {
+        self.state.borrow_mut().alpha = alpha;
+    }
+
+    fn begin_path(&self) {
+        self.state.borrow_mut().path.clear();
+    }
+
+    fn move_to(&self, x: f64, y: f64) {
+        self.state.borrow_mut().path_to('M', x, y);
+    }
+
+    fn line_to(&self, x: f64, y: f64) {
+        let state = &mut *self.state.borrow_mut();
+        let command = if state.path.is_empty() { 'M' } else { 'L' };
+        state.path_to(command, x, y);
+    }
+
+    fn arc(&self, x: f64, y: f64, radius: f64, start: f64, end: f64) {
+        let point = |angle: f64| (x + radius * angle.cos(), y + radius * angle.sin());
+        let (start_x, start_y) = point(start);
+        self.line_to(start_x, start_y);
+        // A single arc command can't draw a full circle, so use two halves
+        let sweep = (end - start).clamp(-2.0 * f64::consts::PI, 2.0 * f64::consts::PI);
+        let sweep_flag = if sweep > 0.0 { 1 } else { 0 };
+        let state = &mut *self.state.borrow_mut();
+        for half in 1..=2 {
+            let (px, py) = point(start + sweep * half as f64 / 2.0);
+            write!(
+                state.path,
+                "A{r:.2} {r:.2} 0 0 {} {:.2} {:.2} ",
+                sweep_flag,
+                px,
+                py,
+                r = radius
+            )
+            .unwrap();
+        }
+    }
+
+    fn rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        let state = &mut *self.state.borrow_mut();
+        state.path_to('M', x, y);
+        state.path_to('L', x + width, y);
+        state.path_to('L', x + width, y + height);
+        state.path_to('L', x, y + height);
+        state.path.push_str("Z ");
+    }
+
+    fn fill(&self) {
+        let state = &mut *self.state.borrow_mut();
+        if state.path.is_empty() {
+            return;
+        }
+        let element = format!(
+            "<path d=\"{}\" {} {}/>\n",
+            state.path.trim_end(),
+            paint("fill", &state.fill),
+            state.attrs()
+        );
+        state.body.push_str(&element);
+    }
+
+    fn stroke(&self) {
+        let state = &mut *self.state.borrow_mut();
+        if state.path.is_empty() {
+            return;
+        }
+        let element = format!(
+            "<path d=\"{}\" fill=\"none\" {} stroke-width=\"{}\" {}/>\n",
+            state.path.trim_end(),
+            paint("stroke", &state.stroke),
+            state.line_width,
+            state.attrs()
+        );
+        state.body.push_str(&element);
+    }
+
+    fn fill_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        let state = &mut *self.state.borrow_mut();
+        // Negative sizes are valid on a canvas, but not in svg
+        let (x, width) = (x.min(x + width), width.abs());
+        let (y, height) = (y.min(y + height), height.abs());
+        let element = format!(
+            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" {} {}/>\n",
+            x,
+            y,
+            width,
+            height,
+            paint("fill", &state.fill),
+            state.attrs()
+        );
+        state.body.push_str(&element);
+    }
+
+    fn fill_text(&self, text: &str, x: f64, y: f64) {
+        let state = &mut *self.state.borrow_mut();
+        // Matches the default font of a canvas
+        let element = format!(
+            "<text x=\"{:.2}\" y=\"{:.2}\" {} font-family=\"sans-serif\" font-size=\"10\" {}>{}</text>\n",
+            x,
+            y,
+            paint("fill", &state.fill),
+            state.attrs(),
+            escape(text)
+        );
+        state.body.push_str(&element);
+    }
+
+    fn draw_image(&self, image: &String, x: f64, y: f64, width: f64, height: f64) {
+        let state = &mut *self.state.borrow_mut();
+        let element = format!(
+            "<image href=\"{}\" x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" {}/>\n",
+            escape(image),
+            x,
+            y,
+            width,
+            height,
+            state.attrs()
+        );
+        state.body.push_str(&element);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{boids::Boids, shape::Appearance};
+
+    #[test]
+    fn boids_become_paths() {
+        let mut boids = Boids::new(200.0, 100.0);
+        boids.boids.truncate(1);
+        let svg = Svg::new(200.0, 100.0);
+        svg.set_fill_style("#4c566a33");
+        svg.begin_path();
+        boids.boids[0].render(&svg, &Appearance::default());
+        svg.fill();
+        let svg = svg.finish();
+        assert!(svg.starts_with("<svg "), "{}", svg);
+        assert!(svg.contains("viewBox=\"0 0 200 100\""), "{}", svg);
+        assert_eq!(svg.matches("<path ").count(), 1, "{}", svg);
+        assert!(
+            svg.contains("fill=\"#4c566a\" fill-opacity=\"0.2\""),
+            "{}",
+            svg
+        );
+    }
+
+    #[test]
+    fn only_colors_with_alpha_are_split() {
+        assert_eq!(paint("stroke", "#123abc"), "stroke=\"#123abc\"");
+        assert_eq!(paint("fill", "white"), "fill=\"white\"");
+        assert_eq!(
+            paint("stroke", "#123abc80"),
+            "stroke=\"#123abc\" stroke-opacity=\"0.502\""
+        );
+    }
+}
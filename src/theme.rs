@@ -0,0 +1,274 @@
+use std::str::FromStr;
+
+use crate::draw::parse_color;
+
+/// Colours used to draw the world, the boids and the debug overlay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub background: String,
+    /// Background of the world in special mode
+    pub special_background: String,
+    /// Everything outside of the world
+    pub outside: String,
+    pub boid: String,
+    pub predator: String,
+    pub align_radius: String,
+    pub cohesion_radius: String,
+    pub seperation_radius: String,
+    pub velocity: String,
+    /// Arrows of the velocity field overlay
+    pub velocity_field: String,
+    /// Steering vectors of an inspected boid, the others use the radius colours
+    pub angst_steer: String,
+    pub return_steer: String,
+    pub border: String,
+    pub bucket_grid: String,
+    /// Text of the overlay
+    pub statistics: String,
+    pub metrics: String,
+    pub simulation_profile: String,
+    pub render_profile: String,
+}
+
+impl Theme {
+    pub fn light() -> Self {
+        Theme {
+            background: "#d8dee9".into(),
+            special_background: "black".into(),
+            outside: "#4c566a".into(),
+            boid: "#bf616a".into(),
+            predator: "red".into(),
+            align_radius: "red".into(),
+            cohesion_radius: "green".into(),
+            seperation_radius: "blue".into(),
+            velocity: "white".into(),
+            velocity_field: "#2e3440".into(),
+            angst_steer: "#b48ead".into(),
+            return_steer: "#d08770".into(),
+            border: "green".into(),
+            bucket_grid: "#4c566a33".into(),
+            statistics: "#666666".into(),
+            metrics: "#5e81ac".into(),
+            simulation_profile: "#a3be8c".into(),
+            render_profile: "#d08770".into(),
+        }
+    }
+
+    pub fn dark() -> Self {
+        Theme {
+            background: "#2e3440".into(),
+            special_background: "black".into(),
+            outside: "#191d24".into(),
+            boid: "#88c0d0".into(),
+            predator: "#bf616a".into(),
+            align_radius: "#bf616a".into(),
+            cohesion_radius: "#a3be8c".into(),
+            seperation_radius: "#81a1c1".into(),
+            velocity: "#eceff4".into(),
+            velocity_field: "#eceff4".into(),
+            angst_steer: "#b48ead".into(),
+            return_steer: "#ebcb8b".into(),
+            border: "#a3be8c".into(),
+            bucket_grid: "#d8dee933".into(),
+            statistics: "#d8dee9".into(),
+            metrics: "#88c0d0".into(),
+            simulation_profile: "#a3be8c".into(),
+            render_profile: "#d08770".into(),
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Theme {
+            background: "#ffffff".into(),
+            special_background: "#000000".into(),
+            outside: "#404040".into(),
+            boid: "#000000".into(),
+            predator: "#d00000".into(),
+            align_radius: "#d00000".into(),
+            cohesion_radius: "#008000".into(),
+            seperation_radius: "#0000d0".into(),
+            velocity: "#8000ff".into(),
+            velocity_field: "#8000ff".into(),
+            angst_steer: "#c000c0".into(),
+            return_steer: "#c06000".into(),
+            border: "#000000".into(),
+            bucket_grid: "#00000055".into(),
+            statistics: "#000000".into(),
+            metrics: "#0000d0".into(),
+            simulation_profile: "#008000".into(),
+            render_profile: "#d00000".into(),
+        }
+    }
+
+    /// Whether the background is dark, so that the overlay
+    /// and the settings panel should be light.
+    pub fn is_dark(&self) -> bool {
+        is_dark(&self.background)
+    }
+}
+
+fn is_dark(color: &str) -> bool {
+    let [r, g, b, _] = parse_color(color);
+    // Relative luminance, ignoring the gamma
+    let luminance = 0.2126 * f64::from(r) + 0.7152 * f64::from(g) + 0.0722 * f64::from(b);
+    luminance < 128.0
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::light()
+    }
+}
+
+named_enum! {
+    /// The themes selectable in the settings panel.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ThemeKind {
+        /// Light or dark, following the colour scheme of the operating system
+        System => ("system", "System"),
+        Light => ("light", "Light"),
+        Dark => ("dark", "Dark"),
+        HighContrast => ("high-contrast", "High Contrast"),
+        /// The light or dark theme with the colours in [`CustomColors`]
+        Custom => ("custom", "Custom"),
+    }
+}
+
+/// The colours chosen for the custom theme, as `#rrggbb`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomColors {
+    pub background: String,
+    pub boid: String,
+    pub predator: String,
+}
+
+impl CustomColors {
+    /// Encode the colours for the url, as hex digits separated by dots.
+    pub fn to_url_value(&self) -> String {
+        [&self.background, &self.boid, &self.predator]
+            .iter()
+            .map(|color| color.trim_start_matches('#'))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+impl Default for CustomColors {
+    fn default() -> Self {
+        let light = Theme::light();
+        CustomColors {
+            background: light.background,
+            boid: light.boid,
+            predator: String::from("#ff0000"),
+        }
+    }
+}
+
+impl FromStr for CustomColors {
+    type Err = ();
+
+    /// Parse the encoding written by [`CustomColors::to_url_value`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let colors: Vec<_> = s.split('.').map(|digits| format!("#{}", digits)).collect();
+        let valid =
+            |color: &String| color.len() == 7 && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+        match &colors[..] {
+            [background, boid, predator] if colors.iter().all(valid) => Ok(CustomColors {
+                background: background.clone(),
+                boid: boid.clone(),
+                predator: predator.clone(),
+            }),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The selected theme, stored in presets and the url.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThemeSettings {
+    pub kind: ThemeKind,
+    pub custom: CustomColors,
+}
+
+impl ThemeSettings {
+    /// The colours to draw with. `prefers_dark` is the colour
+    /// scheme of the operating system.
+    pub fn resolve(&self, prefers_dark: bool) -> Theme {
+        match self.kind {
+            ThemeKind::System if prefers_dark => Theme::dark(),
+            ThemeKind::System | ThemeKind::Light => Theme::light(),
+            ThemeKind::Dark => Theme::dark(),
+            ThemeKind::HighContrast => Theme::high_contrast(),
+            ThemeKind::Custom => {
+                // Keep the overlay readable on the chosen background
+                let base = if is_dark(&self.custom.background) {
+                    Theme::dark()
+                } else {
+                    Theme::light()
+                };
+                Theme {
+                    background: self.custom.background.clone(),
+                    boid: self.custom.boid.clone(),
+                    predator: self.custom.predator.clone(),
+                    ..base
+                }
+            }
+        }
+    }
+
+    /// Url query for a theme other than `base`, starting with `&`. The
+    /// base is the theme a url implies without query, see [`Preset::theme`].
+    ///
+    /// [`Preset::theme`]: crate::presets::Preset::theme
+    pub fn to_url_query(&self, base: ThemeKind) -> String {
+        match self.kind {
+            ThemeKind::Custom => format!(
+                "&theme={}&theme-colors={}",
+                self.kind.name(),
+                self.custom.to_url_value()
+            ),
+            kind if kind == base => String::new(),
+            kind => format!("&theme={}", kind.name()),
+        }
+    }
+}
+
+impl Default for ThemeSettings {
+    fn default() -> Self {
+        ThemeSettings {
+            kind: ThemeKind::System,
+            custom: CustomColors::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_colors_round_trip() {
+        let colors = CustomColors {
+            background: String::from("#102030"),
+            boid: String::from("#AbCdEf"),
+            predator: String::from("#ff0000"),
+        };
+        assert_eq!(colors.to_url_value(), "102030.AbCdEf.ff0000");
+        assert_eq!(colors.to_url_value().parse(), Ok(colors));
+    }
+
+    #[test]
+    fn invalid_custom_colors_are_rejected() {
+        for raw in &[
+            "",
+            "102030.405060",
+            "102030.405060.708090.a0b0c0",
+            "102030.40506.708090",
+            "102030.4050600.708090",
+            "102030.40506g.708090",
+            "#102030.405060.708090",
+        ] {
+            assert_eq!(raw.parse::<CustomColors>(), Err(()), "{}", raw);
+        }
+    }
+}
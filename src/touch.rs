@@ -0,0 +1,130 @@
+use na::Vector2;
+use nalgebra as na;
+use yew::web_sys::{Touch, TouchEvent, TouchList};
+
+use std::collections::HashMap;
+
+use crate::{TAP_MAX_DISTANCE_SQUARED, TAP_MAX_DURATION};
+
+/// Keeps track of the touches on the canvas to tell taps from drags.
+pub struct TouchTracker {
+    /// Start time and position of every active touch by identifier
+    starts: HashMap<i32, (f64, Vector2<f64>)>,
+    /// Set once a second finger touches, until all fingers are lifted.
+    /// Touches of such gestures, like pinches, never count as taps
+    multi_touch: bool,
+}
+
+/// What a touch event changed.
+pub struct TouchUpdate {
+    /// Positions of all touches that are still active
+    pub active: Vec<Vector2<f64>>,
+    /// Positions of touches that ended as taps
+    pub taps: Vec<Vector2<f64>>,
+}
+
+impl TouchTracker {
+    pub fn new() -> Self {
+        TouchTracker {
+            starts: HashMap::new(),
+            multi_touch: false,
+        }
+    }
+
+    pub fn handle(&mut self, event: &TouchEvent, now: f64) -> TouchUpdate {
+        let mut taps = vec![];
+        for touch in touches(&event.changed_touches()) {
+            let pos = position(&touch);
+            match event.type_().as_str() {
+                "touchstart" => self.start(touch.identifier(), pos, now),
+                "touchend" => taps.extend(self.end(touch.identifier(), pos, now)),
+                "touchcancel" => self.cancel(touch.identifier()),
+                _ => {}
+            }
+        }
+        let active = touches(&event.touches())
+            .map(|touch| position(&touch))
+            .collect();
+        TouchUpdate { active, taps }
+    }
+
+    fn start(&mut self, id: i32, pos: Vector2<f64>, now: f64) {
+        self.starts.insert(id, (now, pos));
+        if self.starts.len() > 1 {
+            self.multi_touch = true;
+        }
+    }
+
+    /// End the touch `id` at `pos`, returning the position if it was a tap.
+    fn end(&mut self, id: i32, pos: Vector2<f64>, now: f64) -> Option<Vector2<f64>> {
+        let (start, start_pos) = self.starts.remove(&id)?;
+        let multi_touch = self.multi_touch;
+        if self.starts.is_empty() {
+            self.multi_touch = false;
+        }
+        let moved = (pos - start_pos).norm_squared();
+        Some(pos).filter(|_| {
+            !multi_touch && now - start <= TAP_MAX_DURATION && moved <= TAP_MAX_DISTANCE_SQUARED
+        })
+    }
+
+    fn cancel(&mut self, id: i32) {
+        self.starts.remove(&id);
+        if self.starts.is_empty() {
+            self.multi_touch = false;
+        }
+    }
+}
+
+fn touches(list: &TouchList) -> impl Iterator<Item = Touch> + '_ {
+    (0..list.length()).filter_map(move |idx| list.get(idx))
+}
+
+fn position(touch: &Touch) -> Vector2<f64> {
+    Vector2::new(touch.client_x() as f64, touch.client_y() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_touches_in_place_are_taps() {
+        let mut tracker = TouchTracker::new();
+        let pos = Vector2::new(100.0, 100.0);
+        tracker.start(0, pos, 0.0);
+        let end = pos + Vector2::new(3.0, 4.0);
+        assert_eq!(tracker.end(0, end, TAP_MAX_DURATION), Some(end));
+    }
+
+    #[test]
+    fn long_or_moving_touches_are_drags() {
+        let mut tracker = TouchTracker::new();
+        let pos = Vector2::new(100.0, 100.0);
+        tracker.start(0, pos, 0.0);
+        assert_eq!(tracker.end(0, pos, TAP_MAX_DURATION + 1.0), None);
+        tracker.start(1, pos, 0.0);
+        let far = pos + Vector2::new(TAP_MAX_DISTANCE_SQUARED.sqrt() + 1.0, 0.0);
+        assert_eq!(tracker.end(1, far, 10.0), None);
+    }
+
+    #[test]
+    fn pinches_are_no_taps() {
+        let mut tracker = TouchTracker::new();
+        let (a, b) = (Vector2::new(100.0, 100.0), Vector2::new(200.0, 100.0));
+        tracker.start(0, a, 0.0);
+        tracker.start(1, b, 10.0);
+        assert_eq!(tracker.end(0, a, 50.0), None);
+        // The remaining finger still belongs to the pinch
+        assert_eq!(tracker.end(1, b, 60.0), None);
+        // The next gesture starts fresh
+        tracker.start(2, a, 100.0);
+        assert_eq!(tracker.end(2, a, 150.0), Some(a));
+    }
+
+    #[test]
+    fn unknown_touches_are_ignored() {
+        let mut tracker = TouchTracker::new();
+        assert_eq!(tracker.end(7, Vector2::zeros(), 0.0), None);
+    }
+}
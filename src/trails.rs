@@ -0,0 +1,54 @@
+use crate::{boids::Boids, camera::Camera, coloring::Coloring, draw::Draw};
+
+named_enum! {
+    /// How the recent movement of the boids is visualised.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum TrailMode {
+        Off => ("off", "No Trails"),
+        /// Draw the last positions of every boid as fading line
+        Lines => ("lines", "Trail Lines"),
+        /// Only partially clear the background, so that old frames fade out.
+        /// Always drawn by the Canvas 2D renderer, WebGL falls back to it
+        Fade => ("fade", "Fading Frames"),
+    }
+}
+
+/// Draw the trail of every boid in its colour. Segments start with the given
+/// `opacity` next to the boid and fade out linearly towards the end of the trail.
+pub fn draw_trails(
+    ctx: &impl Draw,
+    boids: &Boids,
+    camera: &Camera,
+    opacity: f64,
+    coloring: &Coloring,
+) {
+    let length = boids.trail_length;
+    if length < 2 {
+        return;
+    }
+    let mut groups = vec![Vec::new(); coloring.palette.len()];
+    for (boid, color) in boids.boids.iter().zip(&coloring.colors) {
+        groups[*color].push(boid);
+    }
+    camera.apply(ctx);
+    // One path per colour and age, since the opacity is the same for all segments in it
+    for (group, color) in groups.iter().zip(&coloring.palette) {
+        if group.is_empty() {
+            continue;
+        }
+        ctx.set_stroke_style(color);
+        for age in 0..length - 1 {
+            ctx.set_global_alpha(opacity * (1.0 - age as f64 / (length - 1) as f64));
+            ctx.begin_path();
+            for boid in group {
+                if let (Some(from), Some(to)) = (boid.trail.get(age), boid.trail.get(age + 1)) {
+                    ctx.move_to(from.x, from.y);
+                    ctx.line_to(to.x, to.y);
+                }
+            }
+            ctx.stroke();
+        }
+    }
+    ctx.set_global_alpha(1.0);
+    camera.reset(ctx);
+}
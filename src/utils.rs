@@ -1,49 +1,171 @@
 use log::warn;
 use wasm_bindgen::{prelude::*, JsCast};
 use yew::{
+    callback::Callback,
     utils::{document, window},
-    web_sys::{HtmlImageElement, Performance, Url},
+    web_sys::{
+        Blob, BlobPropertyBag, CanvasRenderingContext2d, HtmlAnchorElement, HtmlCanvasElement,
+        HtmlImageElement, MediaQueryList, Performance, Url,
+    },
 };
 
 use std::f64;
 
-use crate::{boid::Boid, boids::Boids, QR_CODE_ID};
+use crate::{
+    boid::Boid,
+    boids::Boids,
+    params::{self, Param},
+    presets::Preset,
+    profile::{Phase, Profiler},
+    theme::ThemeSettings,
+    URL_VERSION,
+};
 
-pub fn update_boids_from_url(boids: &mut Boids) {
+/// Read all parameters, the preset they are based on and the theme
+/// from the current url.
+///
+/// Both the readable (version 1 and 2) and the compact encoding are
+/// understood. Urls without a version are version 1 and read with the
+/// legacy keys of [`Param`]. Values outside the limits of the sliders are clamped.
+/// Returns whether the url used the compact encoding.
+pub fn update_from_url(
+    boids: &mut Boids,
+    preset: &mut Option<Preset>,
+    theme: &mut ThemeSettings,
+) -> bool {
     let raw_url = document().url().expect("Failed to get URL");
     let url = Url::new(&raw_url).expect("Failed to create url from string");
-    let params = url.search_params();
-    macro_rules! parse {
-        ($key:literal, $callback:expr) => {
-            if let Some(val) = params.get($key) {
-                if let Ok(val) = val.parse() {
-                    $callback(val)
-                } else {
-                    warn!("Invalid value for key {} in url", stringify!($key));
+    apply_url_query(boids, preset, theme, &url.search())
+}
+
+/// Value of `key` in a url query like `?a=1&b=2`. Values are not
+/// percent decoded, the encodings never need escaping.
+fn query_value<'a>(search: &'a str, key: &str) -> Option<&'a str> {
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .filter_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            Some((parts.next()?, parts.next().unwrap_or("")))
+        })
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| value)
+}
+
+/// Apply the parameters in a url query, see [`update_from_url`].
+fn apply_url_query(
+    boids: &mut Boids,
+    preset: &mut Option<Preset>,
+    theme: &mut ThemeSettings,
+    search: &str,
+) -> bool {
+    let query = |key| query_value(search, key).map(String::from);
+    let version = match query("v") {
+        Some(raw) => raw.parse().unwrap_or_else(|_| {
+            warn!("Invalid url version {}", raw);
+            URL_VERSION
+        }),
+        None => 1,
+    };
+    if version > URL_VERSION {
+        warn!(
+            "Url version {} is newer than the supported version {}",
+            version, URL_VERSION
+        );
+    }
+    // Apply the preset first, so that individual values can override it
+    if let Some(raw) = query("preset") {
+        match raw.parse::<Preset>() {
+            Ok(parsed) => {
+                parsed.apply(boids);
+                if let Some(kind) = parsed.theme() {
+                    theme.kind = kind;
                 }
+                *preset = Some(parsed);
             }
+            Err(_) => warn!("Unknown preset {} in url", raw),
+        }
+    }
+    if let Some(raw) = query("theme") {
+        match raw.parse() {
+            Ok(kind) => theme.kind = kind,
+            Err(_) => warn!("Unknown theme {} in url", raw),
+        }
+    }
+    if let Some(raw) = query("theme-colors") {
+        match raw.parse() {
+            Ok(custom) => theme.custom = custom,
+            Err(_) => warn!("Invalid theme colours {} in url", raw),
+        }
+    }
+    let compact = query("s");
+    if let Some(blob) = &compact {
+        decode_compact(boids, blob);
+    }
+    for param in params::ALL.iter() {
+        // Version 2 renamed some keys, older keys are only read from older urls
+        let raw = match query(param.key) {
+            None if version < 2 => query(param.legacy_key),
+            raw => raw,
         };
+        if let Some(raw) = raw {
+            match raw.parse::<f64>() {
+                Ok(val) if val.is_finite() => set_clamped(boids, param, val),
+                _ => warn!("Invalid value {} for key {} in url", raw, param.key),
+            }
+        }
     }
-    parse!("nr-of-boids", |number: usize| change_number_of_boids(
-        boids, number
-    ));
-    parse!("align-radius", |radius: f64| boids.align_radius_squared =
-        radius.powf(2.0));
-    parse!("cohesion-radius", |radius: f64| boids
-        .cohesion_radius_squared =
-        radius.powf(2.0));
-    parse!("seperation-radius", |radius: f64| boids
-        .seperation_radius_squared =
-        radius.powf(2.0));
-    parse!("angst-radius", |radius: f64| boids.angst_radius_squared =
-        radius.powf(2.0));
-    parse!("max-speed", |speed: f64| boids.max_speed = speed);
-    parse!("max-steer", |steer: f64| boids.max_steer = steer);
-    parse!("align-factor", |fac: f64| boids.align_factor = fac);
-    parse!("cohesion-factor", |fac: f64| boids.cohesion_factor = fac);
-    parse!("seperation-factor", |fac: f64| boids.seperation_factor =
-        fac);
-    parse!("angst-factor", |fac: f64| boids.angst_factor = fac);
+    compact.is_some()
+}
+
+/// Decode the blob written by [`Boids::to_compact_url_suffix`].
+fn decode_compact(boids: &mut Boids, blob: &str) {
+    let bytes = match base64::decode_config(blob, base64::URL_SAFE_NO_PAD) {
+        Ok(bytes) if bytes.len() >= 3 => bytes,
+        _ => {
+            warn!("Invalid compact configuration in url");
+            return;
+        }
+    };
+    if bytes[0] > URL_VERSION {
+        warn!("Compact configuration version {} is unsupported", bytes[0]);
+        return;
+    }
+    let mask = u16::from_le_bytes([bytes[1], bytes[2]]);
+    let mut values = bytes[3..].chunks_exact(4);
+    for (idx, param) in params::ALL.iter().enumerate() {
+        if mask & (1 << idx) == 0 {
+            continue;
+        }
+        if let Some(chunk) = values.next() {
+            let val = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            // Go through the shortest representation of the f32, to
+            // restore values like 0.1 exactly instead of 0.10000000149...
+            let val: f64 = val.to_string().parse().unwrap_or(f64::NAN);
+            if val.is_finite() {
+                set_clamped(boids, param, val);
+            } else {
+                warn!(
+                    "Invalid value for key {} in compact configuration",
+                    param.key
+                );
+            }
+        } else {
+            warn!("Compact configuration in url is truncated");
+            return;
+        }
+    }
+}
+
+fn set_clamped(boids: &mut Boids, param: &Param, val: f64) {
+    let clamped = param.clamp(val);
+    if (clamped - val).abs() > f64::EPSILON {
+        warn!(
+            "Value {} for key {} in url is out of range, using {}",
+            val, param.key, clamped
+        );
+    }
+    (param.set)(boids, clamped);
 }
 
 pub fn update_url(url_suffix: &str) {
@@ -54,6 +176,49 @@ pub fn update_url(url_suffix: &str) {
         .expect("Replacing state failed");
 }
 
+/// Call `callback` whenever the page is hidden or shown again.
+/// The returned closure must be kept alive for as long as the listener is registered.
+pub fn on_visibility_change(callback: Callback<()>) -> Closure<dyn FnMut()> {
+    let listener = Closure::wrap(Box::new(move || callback.emit(())) as Box<dyn FnMut()>);
+    document()
+        .add_event_listener_with_callback("visibilitychange", listener.as_ref().unchecked_ref())
+        .expect("Failed to listen for visibility changes");
+    listener
+}
+
+/// Media query matching if the operating system uses a dark colour scheme.
+fn dark_color_scheme_query() -> Option<MediaQueryList> {
+    window()
+        .match_media("(prefers-color-scheme: dark)")
+        .ok()
+        .flatten()
+}
+
+pub fn prefers_dark_color_scheme() -> bool {
+    dark_color_scheme_query().is_some_and(|query| query.matches())
+}
+
+/// Call `callback` whenever the colour scheme of the operating system changes.
+/// The returned closure must be kept alive for as long as the listener is registered.
+pub fn on_color_scheme_change(callback: Callback<()>) -> Closure<dyn FnMut()> {
+    let listener = Closure::wrap(Box::new(move || callback.emit(())) as Box<dyn FnMut()>);
+    if let Some(query) = dark_color_scheme_query() {
+        query
+            .add_event_listener_with_callback("change", listener.as_ref().unchecked_ref())
+            .expect("Failed to listen for colour scheme changes");
+    }
+    listener
+}
+
+/// Let the style sheet know whether the page is drawn in dark colours.
+pub fn set_page_theme(dark: bool) {
+    if let Some(root) = document().document_element() {
+        let name = if dark { "dark" } else { "light" };
+        root.set_attribute("data-theme", name)
+            .expect("Failed to set the page theme");
+    }
+}
+
 pub fn performance() -> Performance {
     web_sys::window()
         .expect("Could not get window object")
@@ -61,15 +226,23 @@ pub fn performance() -> Performance {
         .expect("Could not get performance object")
 }
 
-pub fn update(boids: &mut Boids, time_passed: f64) {
+pub fn update(boids: &mut Boids, time_passed: f64, profiler: &mut Profiler) {
     let secs = time_passed / 1000.0;
-    // Iterate over all boid indices
-    for idx in 0..boids.boids.len() {
-        Boid::update(idx, boids, secs);
+    Boid::update_all(boids, secs, profiler);
+    let trail_length = boids.trail_length;
+    for boid in &mut boids.boids {
+        if trail_length > 0 {
+            boid.trail.push_front(boid.pos);
+        }
+        boid.trail.truncate(trail_length);
+    }
+    if boids.track_flocks() {
+        profiler.measure(Phase::Flocks, || boids.update_flocks());
     }
 }
 
 pub fn change_number_of_boids(boids: &mut Boids, number: usize) {
+    boids.suspended.clear();
     while number > boids.boids.len() {
         boids.boids.push(Boid::new(boids.size.0, boids.size.1));
     }
@@ -93,10 +266,277 @@ pub fn get_window_size() -> (f64, f64) {
     (width, height)
 }
 
-pub fn get_qrcode() -> HtmlImageElement {
+/// Number of device pixels per CSS pixel, larger than one on HiDPI screens.
+pub fn get_device_pixel_ratio() -> f64 {
+    web_sys::window()
+        .expect("Could not get window object")
+        .device_pixel_ratio()
+}
+
+/// The image with the given id, if it loaded successfully.
+pub fn get_loaded_image(id: &str) -> Option<HtmlImageElement> {
     document()
-        .get_element_by_id(QR_CODE_ID)
-        .unwrap()
+        .get_element_by_id(id)?
+        .dyn_into::<HtmlImageElement>()
+        .ok()
+        .filter(|image| image.complete() && image.natural_width() > 0)
+}
+
+/// Offer `content` to the user as file download.
+pub fn download(filename: &str, mime: &str, content: &[u8]) {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(content));
+    let mut options = BlobPropertyBag::new();
+    options.type_(mime);
+    let blob = Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+        .expect("Failed to create blob");
+    let url = Url::create_object_url_with_blob(&blob).expect("Failed to create object url");
+    let anchor: HtmlAnchorElement = document()
+        .create_element("a")
+        .expect("Failed to create anchor")
+        .dyn_into()
+        .expect("Anchor is not an anchor");
+    anchor.set_href(&url);
+    anchor.set_download(filename);
+    anchor.click();
+    Url::revoke_object_url(&url).expect("Failed to revoke object url");
+}
+
+/// A canvas of the given size that is not part of the page.
+fn create_canvas(width: u32, height: u32) -> (HtmlCanvasElement, CanvasRenderingContext2d) {
+    let canvas: HtmlCanvasElement = document()
+        .create_element("canvas")
+        .expect("Failed to create canvas")
+        .dyn_into()
+        .expect("Canvas is not a canvas");
+    canvas.set_width(width);
+    canvas.set_height(height);
+    let ctx = canvas
+        .get_context("2d")
+        .expect("Failed to get 2d context")
+        .expect("Canvas has no 2d context")
         .dyn_into()
-        .unwrap()
+        .expect("Context is not a 2d context");
+    (canvas, ctx)
+}
+
+/// The loaded `image` as PNG `data:` url, to embed it into documents
+/// that outlive the page. `None` if the image may not be read.
+pub fn image_data_url(image: &HtmlImageElement) -> Option<String> {
+    let (canvas, ctx) = create_canvas(image.natural_width(), image.natural_height());
+    ctx.draw_image_with_html_image_element(image, 0.0, 0.0)
+        .expect("Failed to copy image");
+    match canvas.to_data_url() {
+        Ok(url) => Some(url),
+        Err(why) => {
+            warn!("Could not read image {}: {:?}", image.src(), why);
+            None
+        }
+    }
+}
+
+/// Draw the canvases with the given ids on top of each other, scaled
+/// to `width` times `height` pixels, and return the resulting rgba pixels.
+pub fn capture_canvases(ids: &[&str], width: u32, height: u32) -> Vec<u8> {
+    let (_, ctx) = create_canvas(width, height);
+    for id in ids {
+        let source: HtmlCanvasElement = document()
+            .get_element_by_id(id)
+            .expect("Missing canvas")
+            .dyn_into()
+            .expect("Canvas is not a canvas");
+        ctx.draw_image_with_html_canvas_element_and_dw_and_dh(
+            &source,
+            0.0,
+            0.0,
+            width as f64,
+            height as f64,
+        )
+        .expect("Failed to copy canvas");
+    }
+    ctx.get_image_data(0.0, 0.0, width as f64, height as f64)
+        .expect("Failed to read pixels")
+        .data()
+        .0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::{CustomColors, ThemeKind};
+
+    /// Boids with a few non-default values.
+    fn customised() -> Boids {
+        let mut boids = Boids::new(800.0, 600.0);
+        change_number_of_boids(&mut boids, 250);
+        boids.align_radius_squared = 42.0f64.powf(2.0);
+        boids.max_speed = 123.5;
+        boids.seperation_factor = 0.1;
+        boids
+    }
+
+    fn custom_theme() -> ThemeSettings {
+        ThemeSettings {
+            kind: ThemeKind::Custom,
+            custom: CustomColors {
+                boid: String::from("#123abc"),
+                ..CustomColors::default()
+            },
+        }
+    }
+
+    /// Everything read from a url query.
+    struct Decoded {
+        boids: Boids,
+        preset: Option<Preset>,
+        theme: ThemeSettings,
+        compact: bool,
+    }
+
+    /// Apply `search` to default boids and theme.
+    fn decode(search: &str) -> Decoded {
+        let mut boids = Boids::new(800.0, 600.0);
+        let mut preset = None;
+        let mut theme = ThemeSettings::default();
+        let compact = apply_url_query(&mut boids, &mut preset, &mut theme, search);
+        Decoded {
+            boids,
+            preset,
+            theme,
+            compact,
+        }
+    }
+
+    fn assert_same_params(a: &Boids, b: &Boids) {
+        for param in params::ALL.iter() {
+            let (a, b) = ((param.get)(a), (param.get)(b));
+            assert!((a - b).abs() < 1e-9, "{}: {} != {}", param.key, a, b);
+        }
+    }
+
+    #[test]
+    fn readable_url_round_trip() {
+        let (boids, theme) = (customised(), custom_theme());
+        let decoded = decode(&(boids.to_url_suffix(None) + &theme.to_url_query(ThemeKind::System)));
+        assert!(!decoded.compact);
+        assert_same_params(&boids, &decoded.boids);
+        assert_eq!(decoded.theme, theme);
+    }
+
+    #[test]
+    fn compact_url_round_trip() {
+        let (boids, theme) = (customised(), custom_theme());
+        let decoded =
+            decode(&(boids.to_compact_url_suffix(None) + &theme.to_url_query(ThemeKind::System)));
+        assert!(decoded.compact);
+        assert_same_params(&boids, &decoded.boids);
+        // Restored exactly, despite being stored as f32
+        assert_eq!(decoded.boids.seperation_factor, 0.1);
+        assert_eq!(decoded.theme, theme);
+    }
+
+    #[test]
+    fn default_url_has_only_the_version() {
+        assert_eq!(Boids::new(800.0, 600.0).to_url_suffix(None), "?v=2");
+    }
+
+    #[test]
+    fn applied_presets_have_only_their_name() {
+        for preset in Preset::ALL.iter() {
+            let mut boids = Boids::new(800.0, 600.0);
+            preset.apply(&mut boids);
+            let expected = format!("?v=2&preset={}", preset.name());
+            assert_eq!(boids.to_url_suffix(Some(*preset)), expected);
+        }
+    }
+
+    #[test]
+    fn legacy_keys_are_read() {
+        let decoded = decode("?seperation-radius=30");
+        assert_eq!(decoded.boids.seperation_radius_squared, 900.0);
+    }
+
+    #[test]
+    fn legacy_keys_are_ignored_in_version_2() {
+        let decoded = decode("?v=2&seperation-radius=30");
+        assert_eq!(
+            decoded.boids.seperation_radius_squared,
+            crate::DEFAULT_SEPERATION_RADIUS.powf(2.0)
+        );
+    }
+
+    #[test]
+    fn new_keys_win_over_legacy_keys() {
+        let decoded = decode("?v=2&seperation-radius=30&separation-radius=40");
+        assert_eq!(decoded.boids.seperation_radius_squared, 1600.0);
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        let decoded = decode("?v=2&max-speed=9999&align-radius=-5");
+        assert_eq!(decoded.boids.max_speed, 500.0);
+        assert_eq!(decoded.boids.align_radius_squared, 0.0);
+    }
+
+    #[test]
+    fn invalid_values_are_ignored() {
+        let decoded = decode("?v=2&max-speed=fast&s=%%%&theme=purple&preset=none");
+        assert_eq!(decoded.boids.max_speed, Boids::new(800.0, 600.0).max_speed);
+        assert_eq!(decoded.preset, None);
+        assert_eq!(decoded.theme, ThemeSettings::default());
+    }
+
+    #[test]
+    fn truncated_compact_blob_keeps_decoded_values() {
+        let boids = customised();
+        let suffix = boids.to_compact_url_suffix(None);
+        let blob = query_value(&suffix, "s").unwrap();
+        let bytes = base64::decode_config(blob, base64::URL_SAFE_NO_PAD).unwrap();
+        // Version, mask and the first value only
+        let truncated = base64::encode_config(&bytes[..7], base64::URL_SAFE_NO_PAD);
+        let decoded = decode(&format!("?v=2&s={}", truncated));
+        assert_eq!(decoded.boids.boids.len(), 250);
+    }
+
+    #[test]
+    fn preset_theme_can_be_overridden() {
+        let decoded = decode("?v=2&preset=predator-heavy");
+        assert_eq!(Some(decoded.theme.kind), Preset::PredatorHeavy.theme());
+        let decoded = decode("?v=2&preset=predator-heavy&theme=light");
+        assert_eq!(decoded.theme.kind, ThemeKind::Light);
+        // The system theme differs from the one of the preset
+        let query = decoded.theme.to_url_query(ThemeKind::Dark);
+        assert_eq!(query, "&theme=light");
+    }
+
+    #[test]
+    fn preset_predators_survive_a_round_trip() {
+        let opened = decode("?v=2&preset=predator-heavy&max-speed=123");
+        assert_eq!(opened.preset, Some(Preset::PredatorHeavy));
+        assert_eq!(opened.boids.predators.len(), 4);
+        for suffix in &[
+            opened.boids.to_url_suffix(opened.preset),
+            opened.boids.to_compact_url_suffix(opened.preset),
+        ] {
+            let reloaded = decode(suffix);
+            assert_eq!(reloaded.preset, opened.preset);
+            assert_eq!(reloaded.boids.predators, opened.boids.predators);
+            assert_same_params(&reloaded.boids, &opened.boids);
+        }
+    }
+
+    #[test]
+    fn values_are_relative_to_the_preset() {
+        let mut boids = decode("?v=2&preset=gas").boids;
+        // Back to the default, which differs from the preset
+        boids.align_radius_squared = crate::DEFAULT_ALIGN_RADIUS.powf(2.0);
+        let suffix = boids.to_url_suffix(Some(Preset::Gas));
+        assert_eq!(
+            suffix,
+            format!(
+                "?v=2&preset=gas&align-radius={}",
+                crate::DEFAULT_ALIGN_RADIUS
+            )
+        );
+        assert_same_params(&decode(&suffix).boids, &boids);
+    }
 }
@@ -0,0 +1,299 @@
+use js_sys::Float32Array;
+use na::Vector2;
+use nalgebra as na;
+use wasm_bindgen::JsCast;
+use yew::{
+    utils::document,
+    web_sys::{
+        CanvasRenderingContext2d as Ctx, HtmlCanvasElement, HtmlImageElement,
+        WebGl2RenderingContext as Gl, WebGlBuffer, WebGlProgram, WebGlShader, WebGlUniformLocation,
+        WebGlVertexArrayObject,
+    },
+};
+
+use crate::{
+    draw::parse_color,
+    model::Model,
+    renderer::{CanvasRenderer, Renderer},
+    shape::Shape,
+    trails::TrailMode,
+    GL_CANVAS_ID,
+};
+
+/// Position, heading and colour of a boid
+const FLOATS_PER_INSTANCE: usize = 7;
+
+const VERTEX_SHADER: &str = r#"#version 300 es
+layout(location = 0) in vec2 a_vertex;
+layout(location = 1) in vec2 a_position;
+layout(location = 2) in vec2 a_heading;
+layout(location = 3) in vec3 a_color;
+
+// World position shown in the top left corner
+uniform vec2 u_offset;
+// Device pixels per world unit
+uniform float u_scale;
+// Size of the canvas in device pixels
+uniform vec2 u_size;
+// Size of the boids relative to their default size
+uniform float u_boid_scale;
+
+out vec3 v_color;
+
+void main() {
+    // Rotate the y axis onto the heading
+    vec2 h = a_heading;
+    vec2 v = a_vertex * u_boid_scale;
+    vec2 rotated = vec2(h.y * v.x + h.x * v.y,
+                        -h.x * v.x + h.y * v.y);
+    vec2 screen = (a_position + rotated - u_offset) * u_scale;
+    vec2 clip = screen / u_size * 2.0 - 1.0;
+    gl_Position = vec4(clip.x, -clip.y, 0.0, 1.0);
+    v_color = a_color;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 300 es
+precision mediump float;
+
+in vec3 v_color;
+out vec4 color;
+
+void main() {
+    color = vec4(v_color, 1.0);
+}
+"#;
+
+/// Draws all boids with a single instanced draw call.
+///
+/// The boids are drawn onto a separate canvas below the 2D canvas,
+/// which is cleared and only holds the debug overlay. Special mode, sprites
+/// and shapes other than the triangle are left to the [`CanvasRenderer`].
+/// Boids are always drawn in full detail.
+pub struct WebGlRenderer {
+    canvas: HtmlCanvasElement,
+    gl: Gl,
+    program: WebGlProgram,
+    vao: WebGlVertexArrayObject,
+    instances: WebGlBuffer,
+    offset: WebGlUniformLocation,
+    scale: WebGlUniformLocation,
+    size: WebGlUniformLocation,
+    boid_scale: WebGlUniformLocation,
+    /// Instance data, kept to reuse the allocation
+    data: Vec<f32>,
+}
+
+impl WebGlRenderer {
+    pub fn new() -> Result<Self, String> {
+        let canvas = document()
+            .get_element_by_id(GL_CANVAS_ID)
+            .ok_or("Missing WebGL canvas")?
+            .dyn_into::<HtmlCanvasElement>()
+            .map_err(|_| "WebGL canvas is not a canvas")?;
+        let gl = canvas
+            .get_context("webgl2")
+            .map_err(|_| "Failed to get WebGL 2 context")?
+            .ok_or("WebGL 2 is not supported")?
+            .dyn_into::<Gl>()
+            .map_err(|_| "Context is not a WebGL 2 context")?;
+        let vertex = compile(&gl, Gl::VERTEX_SHADER, VERTEX_SHADER)?;
+        let fragment = compile(&gl, Gl::FRAGMENT_SHADER, FRAGMENT_SHADER)?;
+        let program = link(&gl, &vertex, &fragment)?;
+
+        let vao = gl
+            .create_vertex_array()
+            .ok_or("Failed to create vertex array")?;
+        gl.bind_vertex_array(Some(&vao));
+        // The triangle shared by all boids
+        let shape = gl.create_buffer().ok_or("Failed to create buffer")?;
+        gl.bind_buffer(Gl::ARRAY_BUFFER, Some(&shape));
+        let points: Vec<f32> = Shape::Triangle
+            .outline()
+            .iter()
+            .flat_map(|(x, y)| [*x as f32, *y as f32])
+            .collect();
+        gl.buffer_data_with_array_buffer_view(
+            Gl::ARRAY_BUFFER,
+            &Float32Array::from(&points[..]),
+            Gl::STATIC_DRAW,
+        );
+        gl.enable_vertex_attrib_array(0);
+        gl.vertex_attrib_pointer_with_i32(0, 2, Gl::FLOAT, false, 0, 0);
+        // Position, heading and colour of every boid
+        let instances = gl.create_buffer().ok_or("Failed to create buffer")?;
+        gl.bind_buffer(Gl::ARRAY_BUFFER, Some(&instances));
+        let stride = (FLOATS_PER_INSTANCE * 4) as i32;
+        for (location, size, offset) in [(1, 2, 0), (2, 2, 2), (3, 3, 4)] {
+            gl.enable_vertex_attrib_array(location);
+            gl.vertex_attrib_pointer_with_i32(location, size, Gl::FLOAT, false, stride, offset * 4);
+            gl.vertex_attrib_divisor(location, 1);
+        }
+        gl.bind_vertex_array(None);
+
+        let uniform = |name: &str| {
+            gl.get_uniform_location(&program, name)
+                .ok_or(format!("Missing uniform {}", name))
+        };
+        let offset = uniform("u_offset")?;
+        let scale = uniform("u_scale")?;
+        let size = uniform("u_size")?;
+        let boid_scale = uniform("u_boid_scale")?;
+        Ok(WebGlRenderer {
+            canvas,
+            gl,
+            program,
+            vao,
+            instances,
+            offset,
+            scale,
+            size,
+            boid_scale,
+            data: Vec::new(),
+        })
+    }
+
+    fn clear(&self, color: &str) {
+        let [r, g, b] = to_rgb(color);
+        self.gl.clear_color(r, g, b, 1.0);
+        self.gl.clear(Gl::COLOR_BUFFER_BIT);
+    }
+}
+
+impl Renderer for WebGlRenderer {
+    fn draw_background(&mut self, model: &Model, ctx: &Ctx) {
+        if model.trail_mode == TrailMode::Fade {
+            // Frames only fade on the 2D canvas, which is partly transparent
+            // until enough of them piled up, so nothing may show through
+            self.gl.clear_color(0.0, 0.0, 0.0, 0.0);
+            self.gl.clear(Gl::COLOR_BUFFER_BIT);
+            return CanvasRenderer.draw_background(model, ctx);
+        }
+        if model.special_mode {
+            return CanvasRenderer.draw_background(model, ctx);
+        }
+        let camera = &model.camera;
+        let (width, height) = camera.backing_size();
+        if self.canvas.width() != width || self.canvas.height() != height {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+        }
+        // The 2D canvas only holds the debug overlay
+        ctx.clear_rect(0.0, 0.0, camera.view.0, camera.view.1);
+        self.gl.viewport(0, 0, width as i32, height as i32);
+        self.clear(&model.theme.outside);
+        // Clear the visible part of the world with the background colour
+        let to_device = |world: Vector2<f64>| {
+            let device = camera.to_screen(world) * camera.pixel_ratio;
+            (
+                device.x.round().clamp(0.0, width as f64) as i32,
+                device.y.round().clamp(0.0, height as f64) as i32,
+            )
+        };
+        let (left, top) = to_device(na::zero());
+        let (right, bottom) = to_device(Vector2::new(model.boids.size.0, model.boids.size.1));
+        self.gl.enable(Gl::SCISSOR_TEST);
+        // Scissor boxes start in the bottom left corner
+        self.gl
+            .scissor(left, height as i32 - bottom, right - left, bottom - top);
+        self.clear(&model.theme.background);
+        self.gl.disable(Gl::SCISSOR_TEST);
+    }
+
+    fn draw_boids(&mut self, model: &Model, ctx: &Ctx, sprite: Option<&HtmlImageElement>) {
+        // Sprites that did not load yet are drawn as triangles, too. The level
+        // of detail is ignored, the GPU draws triangles as fast as dots
+        let triangles = matches!(model.appearance.shape, Shape::Triangle | Shape::Sprite);
+        // The special background and trails are drawn on the 2D canvas,
+        // which would cover boids drawn on the GL canvas below it
+        let covered = model.special_mode || model.trail_mode != TrailMode::Off;
+        if sprite.is_some() || !triangles || covered {
+            return CanvasRenderer.draw_boids(model, ctx, sprite);
+        }
+        let boids = &model.boids;
+        self.data.clear();
+        let palette: Vec<_> = model
+            .coloring
+            .palette
+            .iter()
+            .map(|color| to_rgb(color))
+            .collect();
+        for (boid, color) in boids.boids.iter().zip(&model.coloring.colors) {
+            let heading = boid
+                .vel
+                .try_normalize(f64::EPSILON)
+                .unwrap_or_else(Vector2::y);
+            let [r, g, b] = palette[*color];
+            self.data.extend_from_slice(&[
+                boid.pos.x as f32,
+                boid.pos.y as f32,
+                heading.x as f32,
+                heading.y as f32,
+                r,
+                g,
+                b,
+            ]);
+        }
+        let camera = &model.camera;
+        let (width, height) = camera.backing_size();
+        self.gl.use_program(Some(&self.program));
+        self.gl.uniform2f(
+            Some(&self.offset),
+            camera.offset.x as f32,
+            camera.offset.y as f32,
+        );
+        self.gl
+            .uniform1f(Some(&self.scale), (camera.zoom * camera.pixel_ratio) as f32);
+        self.gl
+            .uniform2f(Some(&self.size), width as f32, height as f32);
+        self.gl
+            .uniform1f(Some(&self.boid_scale), model.appearance.scale as f32);
+        self.gl.bind_buffer(Gl::ARRAY_BUFFER, Some(&self.instances));
+        self.gl.buffer_data_with_array_buffer_view(
+            Gl::ARRAY_BUFFER,
+            &Float32Array::from(&self.data[..]),
+            Gl::STREAM_DRAW,
+        );
+        self.gl.bind_vertex_array(Some(&self.vao));
+        self.gl
+            .draw_arrays_instanced(Gl::TRIANGLES, 0, 3, boids.boids.len() as i32);
+        self.gl.bind_vertex_array(None);
+    }
+}
+
+fn compile(gl: &Gl, kind: u32, source: &str) -> Result<WebGlShader, String> {
+    let shader = gl.create_shader(kind).ok_or("Failed to create shader")?;
+    gl.shader_source(&shader, source);
+    gl.compile_shader(&shader);
+    let compiled = gl
+        .get_shader_parameter(&shader, Gl::COMPILE_STATUS)
+        .as_bool()
+        .unwrap_or(false);
+    if compiled {
+        Ok(shader)
+    } else {
+        Err(gl.get_shader_info_log(&shader).unwrap_or_default())
+    }
+}
+
+fn link(gl: &Gl, vertex: &WebGlShader, fragment: &WebGlShader) -> Result<WebGlProgram, String> {
+    let program = gl.create_program().ok_or("Failed to create program")?;
+    gl.attach_shader(&program, vertex);
+    gl.attach_shader(&program, fragment);
+    gl.link_program(&program);
+    let linked = gl
+        .get_program_parameter(&program, Gl::LINK_STATUS)
+        .as_bool()
+        .unwrap_or(false);
+    if linked {
+        Ok(program)
+    } else {
+        Err(gl.get_program_info_log(&program).unwrap_or_default())
+    }
+}
+
+/// Colour channels in the range `0..=1`.
+fn to_rgb(color: &str) -> [f32; 3] {
+    let [r, g, b, _] = parse_color(color);
+    [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0]
+}